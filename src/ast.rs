@@ -1,51 +1,326 @@
-use crate::{exception, token::{Literal, Token}};
+use std::{fmt, rc::Rc};
 
+use crate::{span::Span, token::{Literal, Token}};
+
+// Printing can't really fail, the result is here so the printers can grow checks later.
+type Result<T> = std::result::Result<T, fmt::Error>;
+
+// Every node keeps the span of the source it came from, so errors can point at it.
 #[derive(Debug, Clone)]
 pub enum Expression {
+    // `depth` is how many scopes out the variable lives, filled by the resolver.
+    // None means it's a global.
+    Assign {
+        name: Token,
+        value: Box<Expression>,
+        depth: Option<usize>,
+        span: Span,
+    },
     Binary {
         operator: Token,
         left: Box<Expression>,
         right: Box<Expression>,
+        span: Span,
+    },
+    // `paren` is the closing one, errors about the call point there.
+    Call {
+        callee: Box<Expression>,
+        paren: Token,
+        arguments: Vec<Expression>,
+        span: Span,
+    },
+    // `left, right`: runs left for its side effects and gives right.
+    Comma {
+        left: Box<Expression>,
+        right: Box<Expression>,
+        span: Span,
+    },
+    // `condition ? then_branch : else_branch`, only one of the branches runs.
+    Conditional {
+        condition: Box<Expression>,
+        then_branch: Box<Expression>,
+        else_branch: Box<Expression>,
+        span: Span,
+    },
+    // `object.name`, reading a property.
+    Get {
+        object: Box<Expression>,
+        name: Token,
+        span: Span,
     },
     Grouping {
         expression: Box<Expression>,
+        span: Span,
     },
     Literal {
         value: Literal,
+        span: Span,
+    },
+    // `and` and `or`, kept apart from Binary because they short-circuit.
+    Logical {
+        operator: Token,
+        left: Box<Expression>,
+        right: Box<Expression>,
+        span: Span,
+    },
+    // `object.name = value`.
+    Set {
+        object: Box<Expression>,
+        name: Token,
+        value: Box<Expression>,
+        span: Span,
+    },
+    // `super.method`, resolved like a variable named `super`.
+    Super {
+        keyword: Token,
+        method: Token,
+        depth: Option<usize>,
+        span: Span,
+    },
+    // Resolved like a variable, the method's closure defines it.
+    This {
+        keyword: Token,
+        depth: Option<usize>,
+        span: Span,
     },
     Unary {
         operator: Token,
         right: Box<Expression>,
+        span: Span,
+    },
+    Variable {
+        name: Token,
+        depth: Option<usize>,
+        span: Span,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Block {
+        statements: Vec<Stmt>,
+        span: Span,
+    },
+    // `label` is the loop to leave, the innermost one when there's none.
+    Break {
+        label: Option<Token>,
+        span: Span,
+    },
+    // `superclass` is always a Variable.
+    Class {
+        name: Token,
+        superclass: Option<Expression>,
+        methods: Vec<Rc<FunctionDeclaration>>,
+        span: Span,
+    },
+    Continue {
+        label: Option<Token>,
+        span: Span,
+    },
+    Expression {
+        expression: Expression,
+        span: Span,
     },
+    // Shared with the function values made from it, so calling doesn't copy the body.
+    Function {
+        declaration: Rc<FunctionDeclaration>,
+    },
+    If {
+        condition: Expression,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+        span: Span,
+    },
+    Print {
+        expression: Expression,
+        span: Span,
+    },
+    Return {
+        keyword: Token,
+        value: Option<Expression>,
+        span: Span,
+    },
+    Var {
+        name: Token,
+        initializer: Option<Expression>,
+        span: Span,
+    },
+    // `increment` only comes from a desugared for, it runs after the body even when
+    // the body does a `continue`.
+    While {
+        label: Option<Token>,
+        condition: Expression,
+        body: Box<Stmt>,
+        increment: Option<Expression>,
+        span: Span,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionDeclaration {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Function { declaration } => declaration.span,
+            Self::Block { span, .. }
+            | Self::Break { span, .. }
+            | Self::Class { span, .. }
+            | Self::Continue { span, .. }
+            | Self::Expression { span, .. }
+            | Self::If { span, .. }
+            | Self::Print { span, .. }
+            | Self::Return { span, .. }
+            | Self::Var { span, .. }
+            | Self::While { span, .. } => *span,
+        }
+    }
+
+    // Expression statements print as the bare expression, the rest get their keyword in front.
+    pub fn print(&self) -> Result<String> {
+        match self {
+            Self::Block { statements, .. } => {
+                let mut builder = String::from("(block");
+                for statement in statements {
+                    builder.push(' ');
+                    builder.push_str(&statement.print()?);
+                }
+                builder.push(')');
+                Ok(builder)
+            },
+            Self::Break { label, .. } => Ok(jump("break", label)),
+            Self::Class { name, superclass, methods, .. } => {
+                let mut builder = format!("(class {}", name.lexeme);
+                if let Some(superclass) = superclass {
+                    builder.push_str(&format!(" < {}", superclass.print()?));
+                }
+                for method in methods {
+                    builder.push(' ');
+                    builder.push_str(&function(method)?);
+                }
+                builder.push(')');
+                Ok(builder)
+            },
+            Self::Continue { label, .. } => Ok(jump("continue", label)),
+            Self::Expression { expression, .. } => expression.print(),
+            Self::Function { declaration } => function(declaration),
+            Self::If { condition, then_branch, else_branch: Some(else_branch), .. } => {
+                Ok(format!("(if {} {} {})", condition.print()?, then_branch.print()?, else_branch.print()?))
+            },
+            Self::If { condition, then_branch, else_branch: None, .. } => {
+                Ok(format!("(if {} {})", condition.print()?, then_branch.print()?))
+            },
+            Self::Print { expression, .. } => Ok(format!("(print {})", expression.print()?)),
+            Self::Return { value: Some(value), .. } => Ok(format!("(return {})", value.print()?)),
+            Self::Return { value: None, .. } => Ok("(return)".to_string()),
+            Self::Var { name, initializer: Some(initializer), .. } => Ok(format!("(var {} {})", name.lexeme, initializer.print()?)),
+            Self::Var { name, initializer: None, .. } => Ok(format!("(var {})", name.lexeme)),
+            Self::While { label, condition, body, increment, .. } => {
+                let mut builder = format!("(while {} {}", condition.print()?, body.print()?);
+                if let Some(increment) = increment {
+                    builder.push(' ');
+                    builder.push_str(&increment.print()?);
+                }
+                builder.push(')');
+
+                match label {
+                    Some(label) => Ok(format!("(label {} {})", label.lexeme, builder)),
+                    None => Ok(builder),
+                }
+            },
+        }
+    }
+}
+
+fn function(declaration: &FunctionDeclaration) -> Result<String> {
+    let params: Vec<&str> = declaration.params.iter().map(|param| param.lexeme.as_str()).collect();
+    let mut builder = format!("(fun {} ({})", declaration.name.lexeme, params.join(" "));
+    for statement in &declaration.body {
+        builder.push(' ');
+        builder.push_str(&statement.print()?);
+    }
+    builder.push(')');
+    Ok(builder)
+}
+
+fn jump(keyword: &str, label: &Option<Token>) -> String {
+    match label {
+        Some(label) => format!("({} {})", keyword, label.lexeme),
+        None => format!("({})", keyword),
+    }
 }
 
 impl Expression {
-    pub fn accept(&self, expr: &Expression) -> exception::Result<String> {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Assign { span, .. }
+            | Self::Binary { span, .. }
+            | Self::Call { span, .. }
+            | Self::Comma { span, .. }
+            | Self::Conditional { span, .. }
+            | Self::Get { span, .. }
+            | Self::Grouping { span, .. }
+            | Self::Literal { span, .. }
+            | Self::Logical { span, .. }
+            | Self::Set { span, .. }
+            | Self::Super { span, .. }
+            | Self::This { span, .. }
+            | Self::Unary { span, .. }
+            | Self::Variable { span, .. } => *span,
+        }
+    }
+
+    pub fn accept(&self, expr: &Expression) -> Result<String> {
         match expr {
-            Self::Binary { operator, left, right, } => {
+            Self::Assign { name, value, .. } => {
+                self.parenthesize(&format!("= {}", name.lexeme), vec![*value.clone()])
+            },
+            Self::Binary { operator, left, right, .. } => {
                 self.parenthesize(&operator.lexeme, vec![*left.clone(), *right.clone()])
             },
-            Self::Grouping { expression } => {
+            Self::Call { callee, arguments, .. } => {
+                let mut exprs = vec![*callee.clone()];
+                exprs.extend(arguments.iter().cloned());
+                self.parenthesize("call", exprs)
+            },
+            Self::Comma { left, right, .. } => {
+                self.parenthesize(",", vec![*left.clone(), *right.clone()])
+            },
+            Self::Conditional { condition, then_branch, else_branch, .. } => {
+                self.parenthesize("?:", vec![*condition.clone(), *then_branch.clone(), *else_branch.clone()])
+            },
+            Self::Get { object, name, .. } => {
+                self.parenthesize(&format!(". {}", name.lexeme), vec![*object.clone()])
+            },
+            Self::Grouping { expression, .. } => {
                 self.parenthesize("group", vec![*expression.clone()])
             },
-            Self::Literal { value } => {
-                if value.eq(&Literal::None) {
-                    ()
-                }
-
-                Ok(value.to_string())
-            }
-            Self::Unary { operator, right } => {
+            Self::Literal { value, .. } => Ok(value.to_string()),
+            Self::Logical { operator, left, right, .. } => {
+                self.parenthesize(&operator.lexeme, vec![*left.clone(), *right.clone()])
+            },
+            Self::Set { object, name, value, .. } => {
+                self.parenthesize(&format!("=. {}", name.lexeme), vec![*object.clone(), *value.clone()])
+            },
+            Self::Super { method, .. } => Ok(format!("(super {})", method.lexeme)),
+            Self::This { .. } => Ok("this".to_string()),
+            Self::Unary { operator, right, .. } => {
                 self.parenthesize(&operator.lexeme, vec![*right.clone()])
             },
+            Self::Variable { name, .. } => Ok(name.lexeme.clone()),
         }
     }
 
-    pub fn print(&self) -> exception::Result<String> {
+    pub fn print(&self) -> Result<String> {
         self.accept(self)
     }
 
-    fn parenthesize(&self, name: &str, exprs: Vec<Expression>) -> exception::Result<String> {
+    fn parenthesize(&self, name: &str, exprs: Vec<Expression>) -> Result<String> {
         let mut builder: String = String::new();
 
         builder.push('(');
@@ -61,28 +336,71 @@ impl Expression {
         Ok(builder)
     }
 
-    fn reverse_polish_notation(&self) -> exception::Result<String> {
+    pub fn reverse_polish_notation(&self) -> Result<String> {
         let mut builder: String = String::new();
         match self {
-            // For a binary operator (e.g., +, -, *, /)
-            Expression::Binary { operator, left, right } => {
+            Expression::Assign { name, value, .. } => {
+                let value_rpn = value.reverse_polish_notation();
+                builder.push_str(&format!("{} {} =", name.lexeme, value_rpn.unwrap()));
+                Ok(builder)
+            }
+            // For a binary operator (e.g., +, -, *, /, and, or)
+            Expression::Binary { operator, left, right, .. }
+            | Expression::Logical { operator, left, right, .. } => {
                 let left_rpn = left.reverse_polish_notation();
                 let right_rpn = right.reverse_polish_notation();
 
                 builder.push_str(&format!("{} {} {}", left_rpn.unwrap(), right_rpn.unwrap(), operator.lexeme));
                 Ok(builder)
             }
-            Expression::Grouping { expression } => {
+            // The arguments first, then what's called: f(1, 2) is "1 2 f call".
+            Expression::Call { callee, arguments, .. } => {
+                for argument in arguments {
+                    builder.push_str(&argument.reverse_polish_notation()?);
+                    builder.push(' ');
+                }
+                builder.push_str(&format!("{} call", callee.reverse_polish_notation()?));
+                Ok(builder)
+            }
+            Expression::Comma { left, right, .. } => {
+                builder.push_str(&format!("{} {} ,", left.reverse_polish_notation()?, right.reverse_polish_notation()?));
+                Ok(builder)
+            }
+            // a ? b : c is "a b c ?:".
+            Expression::Conditional { condition, then_branch, else_branch, .. } => {
+                builder.push_str(&format!(
+                    "{} {} {} ?:",
+                    condition.reverse_polish_notation()?,
+                    then_branch.reverse_polish_notation()?,
+                    else_branch.reverse_polish_notation()?
+                ));
+                Ok(builder)
+            }
+            // p.x is "p x ." and p.x = 1 is "p 1 x .=".
+            Expression::Get { object, name, .. } => {
+                builder.push_str(&format!("{} {} .", object.reverse_polish_notation()?, name.lexeme));
+                Ok(builder)
+            }
+            Expression::Set { object, name, value, .. } => {
+                builder.push_str(&format!("{} {} {} .=", object.reverse_polish_notation()?, value.reverse_polish_notation()?, name.lexeme));
+                Ok(builder)
+            }
+            Expression::Super { method, .. } => Ok(format!("super {} .", method.lexeme)),
+            Expression::This { .. } => Ok("this".to_string()),
+            Expression::Grouping { expression, .. } => {
                 expression.reverse_polish_notation()
             }
-            Expression::Literal { value } => {
+            Expression::Literal { value, .. } => {
                 Ok(value.to_string())
             }
-            Expression::Unary { operator, right } => {
+            Expression::Unary { operator, right, .. } => {
                 let right_rpn = right.reverse_polish_notation();
                 builder.push_str(&format!("{} {}", right_rpn.unwrap(), operator.lexeme));
                 Ok(builder)
             }
+            Expression::Variable { name, .. } => {
+                Ok(name.lexeme.clone())
+            }
         }
     }
 }
@@ -94,7 +412,7 @@ mod tests {
 
     #[test]
     fn test_literal() {
-        let parser = Expression::print(&Expression::Literal { value: Literal::String("teste".to_string()) });
+        let parser = Expression::print(&Expression::Literal { value: Literal::String("teste".to_string()), span: Span::default() });
 
         assert!(parser.is_ok());
     }
@@ -102,15 +420,18 @@ mod tests {
     #[test]
     fn test_numbers() {
         let expression = Expression::Binary {
-            operator: Token::new(TokenEnum::Star, "*", Literal::None, 1),
+            operator: Token::new(TokenEnum::Star, "*", Literal::None, Span::default()),
             left: Box::new(
                 Expression::Unary {
-                         operator: Token::new(TokenEnum::Minus, "-", Literal::None, 1),
-                         right: Box::new(Expression::Literal { value: Literal::Number(123) })
+                         operator: Token::new(TokenEnum::Minus, "-", Literal::None, Span::default()),
+                         right: Box::new(Expression::Literal { value: Literal::Number(123), span: Span::default() }),
+                         span: Span::default(),
                     }),
             right: Box::new(Expression::Grouping {
-                expression: Box::new(Expression::Literal { value: Literal::Number(123) })
-            })
+                expression: Box::new(Expression::Literal { value: Literal::Number(123), span: Span::default() }),
+                span: Span::default(),
+            }),
+            span: Span::default(),
         };
 
         let parser = Expression::print(&expression);
@@ -118,24 +439,39 @@ mod tests {
         assert_eq!(parser.unwrap(), "(* (- 123) (group 123))")
     }
 
+    #[test]
+    fn test_statements() {
+        let expression = Expression::Literal { value: Literal::Number(1), span: Span::default() };
+        let print = Stmt::Print { expression: expression.clone(), span: Span::default() };
+        let statement = Stmt::Expression { expression, span: Span::default() };
+
+        assert_eq!(print.print().unwrap(), "(print 1)");
+        assert_eq!(statement.print().unwrap(), "1");
+    }
+
     #[test]
     fn test_reverse_polish_notation() {
         let expression: Expression = Expression::Binary {
-            operator: Token::new(TokenEnum::Star, "*", Literal::None, 1),
+            operator: Token::new(TokenEnum::Star, "*", Literal::None, Span::default()),
             left: Box::new(Expression::Grouping {
                 expression: Box::new(Expression::Binary {
-                    operator: Token::new(TokenEnum::Plus, "+", Literal::None, 1),
-                    left: Box::new(Expression::Literal { value: Literal::Number(1) }),
-                    right: Box::new(Expression::Literal { value: Literal::Number(2) })
-                })
+                    operator: Token::new(TokenEnum::Plus, "+", Literal::None, Span::default()),
+                    left: Box::new(Expression::Literal { value: Literal::Number(1), span: Span::default() }),
+                    right: Box::new(Expression::Literal { value: Literal::Number(2), span: Span::default() }),
+                    span: Span::default(),
+                }),
+                span: Span::default(),
             }),
             right: Box::new(Expression::Grouping {
                 expression: Box::new(Expression::Binary {
-                    operator: Token::new(TokenEnum::Minus, "-", Literal::None, 1),
-                    left: Box::new(Expression::Literal { value: Literal::Number(4) }),
-                    right: Box::new(Expression::Literal { value: Literal::Number(3) })
-                })
-            })
+                    operator: Token::new(TokenEnum::Minus, "-", Literal::None, Span::default()),
+                    left: Box::new(Expression::Literal { value: Literal::Number(4), span: Span::default() }),
+                    right: Box::new(Expression::Literal { value: Literal::Number(3), span: Span::default() }),
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            }),
+            span: Span::default(),
         };
 
         let parser = Expression::reverse_polish_notation(&expression);
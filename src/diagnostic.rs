@@ -0,0 +1,436 @@
+use std::fmt::Write;
+
+use crate::span::Span;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+// A piece of the source we want to point at. The primary label is where the problem
+// is, the secondary ones give context (like where a paren was opened).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: &str) -> Self {
+        Self {
+            severity,
+            code: None,
+            message: message.to_string(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn error(message: &str) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: &str) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label { span, message: message.to_string(), primary: true });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label { span, message: message.to_string(), primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help.push(help.to_string());
+        self
+    }
+
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels.iter().find(|label| label.primary).map(|label| label.span)
+    }
+}
+
+// Turns diagnostics into text like rustc does: the message, where it happened,
+// the source lines involved and carets under the exact span.
+//
+// error[T0001]: Unexpected character '#'.
+//  --> script.tx:1:11
+//   |
+// 1 | var a = 1 # 2;
+//   |           ^
+pub struct Renderer<'a> {
+    source: &'a str,
+    name: &'a str,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(source: &'a str, name: &'a str) -> Self {
+        Self { source, name, color: false }
+    }
+
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+        let severity = diagnostic.severity;
+
+        let title = match diagnostic.code {
+            Some(code) => format!("{}[{}]", severity.as_str(), code),
+            None => severity.as_str().to_string(),
+        };
+        let _ = writeln!(out, "{}: {}", self.paint(&title, severity.color()), self.paint(&diagnostic.message, BOLD));
+
+        let mut labels: Vec<&Label> = diagnostic.labels.iter().collect();
+        labels.sort_by_key(|label| (label.span.line, !label.primary, label.span.column));
+
+        let last_line = labels.iter().map(|label| self.end_line(label.span)).max().unwrap_or(0);
+        let width = last_line.to_string().len();
+        let gutter = self.paint("|", BLUE);
+
+        if let Some(span) = diagnostic.primary_span().or(labels.first().map(|label| label.span)) {
+            let _ = writeln!(out, "{:width$}{} {}:{}", "", self.paint("-->", BLUE), self.name, span);
+        }
+
+        if !labels.is_empty() {
+            let _ = writeln!(out, "{:width$} {}", "", gutter);
+        }
+
+        let mut shown = 0;
+        for label in &labels {
+            for line in label.span.line..=self.end_line(label.span) {
+                if line > shown {
+                    let number = self.paint(&format!("{:>width$}", line), BLUE);
+                    let _ = writeln!(out, "{} {} {}", number, gutter, self.line_text(line).trim_end());
+                    shown = line;
+                }
+
+                let Some((indent, length)) = self.underline(label.span, line) else {
+                    continue;
+                };
+
+                let (marker, color) = if label.primary { ('^', severity.color()) } else { ('-', BLUE) };
+                let mut marks = marker.to_string().repeat(length);
+                if line == self.end_line(label.span) && !label.message.is_empty() {
+                    marks.push(' ');
+                    marks.push_str(&label.message);
+                }
+
+                let _ = writeln!(out, "{:width$} {} {}{}", "", gutter, indent, self.paint(&marks, color));
+            }
+        }
+
+        for note in &diagnostic.notes {
+            let _ = writeln!(out, "{:width$} {} {}: {}", "", self.paint("=", BLUE), self.paint("note", BOLD), note);
+        }
+
+        for help in &diagnostic.help {
+            let _ = writeln!(out, "{:width$} {} {}: {}", "", self.paint("=", BLUE), self.paint("help", BOLD), help);
+        }
+
+        out
+    }
+
+    // One JSON object per diagnostic, on a single line, for editors and CI. The schema:
+    //
+    // {"severity": "error" | "warning", "code": string | null, "message": string,
+    //  "file": string, "span": SPAN | null, "labels": [{"span": SPAN, "message": string, "primary": bool}],
+    //  "notes": [string], "help": [string]}
+    //
+    // where SPAN is {"start", "end", "line", "column", "end_line", "end_column"}. Offsets are bytes
+    // and end exclusive, lines and columns start at 1 and end_column points after the last character.
+    pub fn json(&self, diagnostic: &Diagnostic) -> String {
+        let span = match diagnostic.primary_span() {
+            Some(span) => self.json_span(span),
+            None => "null".to_string(),
+        };
+
+        let labels: Vec<String> = diagnostic
+            .labels
+            .iter()
+            .map(|label| {
+                format!(
+                    "{{\"span\":{},\"message\":{},\"primary\":{}}}",
+                    self.json_span(label.span),
+                    json_string(&label.message),
+                    label.primary
+                )
+            })
+            .collect();
+
+        let code = match diagnostic.code {
+            Some(code) => json_string(code),
+            None => "null".to_string(),
+        };
+
+        format!(
+            "{{\"severity\":{},\"code\":{},\"message\":{},\"file\":{},\"span\":{},\"labels\":[{}],\"notes\":{},\"help\":{}}}",
+            json_string(diagnostic.severity.as_str()),
+            code,
+            json_string(&diagnostic.message),
+            json_string(self.name),
+            span,
+            labels.join(","),
+            json_strings(&diagnostic.notes),
+            json_strings(&diagnostic.help)
+        )
+    }
+
+    fn json_span(&self, span: Span) -> String {
+        let end = span.end.min(self.source.len());
+        let end_line = span.line + self.source.get(span.start..end).unwrap_or("").matches('\n').count() as u32;
+        let line_start = self.line_start(end_line).min(end);
+        let end_column = self.source.get(line_start..end).map_or(span.column, |text| text.chars().count() as u32 + 1);
+
+        format!(
+            "{{\"start\":{},\"end\":{},\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{}}}",
+            span.start, span.end, span.line, span.column, end_line, end_column
+        )
+    }
+
+    fn paint(&self, text: &str, color: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn line_text(&self, line: u32) -> &'a str {
+        self.source.split('\n').nth(line as usize - 1).unwrap_or("")
+    }
+
+    // Byte offset where `line` begins.
+    fn line_start(&self, line: u32) -> usize {
+        if line <= 1 {
+            return 0;
+        }
+
+        self.source
+            .match_indices('\n')
+            .nth(line as usize - 2)
+            .map_or(self.source.len(), |(index, _)| index + 1)
+    }
+
+    fn end_line(&self, span: Span) -> u32 {
+        let end = span.end.min(self.source.len()).max(span.start.min(self.source.len()));
+        let inside = self.source.get(span.start..end).unwrap_or("");
+        // A span ending right after a newline (like an unterminated string) stays on its last real line.
+        span.line + inside.trim_end_matches('\n').matches('\n').count() as u32
+    }
+
+    // The whitespace before the carets and how many carets go under `line` for `span`.
+    // Tabs are kept so the carets line up with what the terminal shows.
+    fn underline(&self, span: Span, line: u32) -> Option<(String, usize)> {
+        let text = self.line_text(line).trim_end_matches('\r');
+        let line_start = self.line_start(line);
+
+        let from = if line == span.line { span.start.saturating_sub(line_start) } else { 0 };
+        let from = from.min(text.len());
+        let to = span.end.saturating_sub(line_start).min(text.len()).max(from);
+
+        let indent: String = text
+            .get(..from)?
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let mut length = text.get(from..to)?.chars().count();
+        if line != span.line && length == 0 {
+            return None;
+        }
+        if length == 0 {
+            // Empty spans (like the end of the file) still get one caret.
+            length = 1;
+        }
+
+        Some((indent, length))
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+fn json_strings(texts: &[String]) -> String {
+    let texts: Vec<String> = texts.iter().map(|text| json_string(text)).collect();
+    format!("[{}]", texts.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_at_the_span() {
+        let source = "var a = 1 # 2;";
+        let diagnostic = Diagnostic::error("Unexpected character '#'.")
+            .with_code("T0001")
+            .with_label(Span::new(10, 11, 1, 11), "");
+
+        assert_eq!(
+            Renderer::new(source, "script.tx").render(&diagnostic),
+            "\
+error[T0001]: Unexpected character '#'.
+ --> script.tx:1:11
+  |
+1 | var a = 1 # 2;
+  |           ^
+"
+        );
+    }
+
+    #[test]
+    fn secondary_labels_notes_and_help() {
+        let source = "print (1 +\n  2;";
+        let diagnostic = Diagnostic::error("Expect ')' after expression.")
+            .with_label(Span::new(14, 15, 2, 4), "expected ')'")
+            .with_secondary(Span::new(6, 7, 1, 7), "to match this")
+            .with_note("groupings must be closed")
+            .with_help("add a ')'");
+
+        assert_eq!(
+            Renderer::new(source, "<stdin>").render(&diagnostic),
+            "\
+error: Expect ')' after expression.
+ --> <stdin>:2:4
+  |
+1 | print (1 +
+  |       - to match this
+2 |   2;
+  |    ^ expected ')'
+  = note: groupings must be closed
+  = help: add a ')'
+"
+        );
+    }
+
+    #[test]
+    fn spans_over_many_lines() {
+        let source = "1 + \"abc\ndef\"";
+        let diagnostic = Diagnostic::error("Operand must be a number.").with_label(Span::new(4, 13, 1, 5), "a string");
+
+        assert_eq!(
+            Renderer::new(source, "a.tx").render(&diagnostic),
+            "\
+error: Operand must be a number.
+ --> a.tx:1:5
+  |
+1 | 1 + \"abc
+  |     ^^^^
+2 | def\"
+  | ^^^^ a string
+"
+        );
+    }
+
+    #[test]
+    fn json_schema() {
+        let source = "print (1 +\n  2;";
+        let diagnostic = Diagnostic::error("Expect ')' after expression.")
+            .with_code("T0101")
+            .with_label(Span::new(14, 15, 2, 4), "")
+            .with_secondary(Span::new(6, 7, 1, 7), "to match \"(\"")
+            .with_note("a\tb");
+
+        assert_eq!(
+            Renderer::new(source, "dir\\a.tx").json(&diagnostic),
+            concat!(
+                r#"{"severity":"error","code":"T0101","message":"Expect ')' after expression.","file":"dir\\a.tx","#,
+                r#""span":{"start":14,"end":15,"line":2,"column":4,"end_line":2,"end_column":5},"#,
+                r#""labels":[{"span":{"start":14,"end":15,"line":2,"column":4,"end_line":2,"end_column":5},"message":"","primary":true},"#,
+                r#"{"span":{"start":6,"end":7,"line":1,"column":7,"end_line":1,"end_column":8},"message":"to match \"(\"","primary":false}],"#,
+                r#""notes":["a\tb"],"help":[]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn json_multi_line_span() {
+        let source = "1 + \"abc\ndef\"";
+        let diagnostic = Diagnostic::warning("w").with_label(Span::new(4, 13, 1, 5), "");
+        let json = Renderer::new(source, "a.tx").json(&diagnostic);
+
+        assert!(json.starts_with(r#"{"severity":"warning","code":null,"#));
+        assert!(json.contains(r#""span":{"start":4,"end":13,"line":1,"column":5,"end_line":2,"end_column":5}"#));
+    }
+
+    #[test]
+    fn colors() {
+        let diagnostic = Diagnostic::warning("unused").with_label(Span::new(0, 1, 1, 1), "");
+        let plain = Renderer::new("a", "a.tx").render(&diagnostic);
+        let colored = Renderer::new("a", "a.tx").color(true).render(&diagnostic);
+
+        assert!(!plain.contains('\x1b'));
+        assert!(colored.starts_with("\x1b[1;33mwarning\x1b[0m"));
+    }
+}
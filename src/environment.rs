@@ -0,0 +1,146 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{error_code, exception::RuntimeError, token::Token, value::Value};
+
+// Where the variables live while the program runs. Every block gets its own environment
+// pointing at the one around it, lookups walk outwards until they find the name.
+// They're shared (Rc<RefCell>) because more than one scope can point at the same parent.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    // Always defines in this scope, shadowing any outer variable with the same name.
+    // Defining an existing name again just replaces it, handy in the prompt.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        match (self.values.get(&name.lexeme), &self.enclosing) {
+            (Some(value), _) => Ok(value.clone()),
+            (None, Some(enclosing)) => enclosing.borrow().get(name),
+            (None, None) => Self::undefined(name),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        match (self.values.get_mut(&name.lexeme), &self.enclosing) {
+            (Some(slot), _) => {
+                *slot = value;
+                Ok(())
+            }
+            (None, Some(enclosing)) => enclosing.borrow_mut().assign(name, value),
+            (None, None) => Self::undefined(name),
+        }
+    }
+
+    // Only this scope, without walking outwards.
+    pub fn get_own(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    // Like `get`, but straight from the scope `distance` steps out, where the resolver
+    // found the variable.
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value, RuntimeError> {
+        if distance == 0 {
+            return self.values.get(&name.lexeme).cloned().map_or_else(|| Self::undefined(name), Ok);
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_at(distance - 1, name),
+            None => Self::undefined(name),
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if distance == 0 {
+            return match self.values.get_mut(&name.lexeme) {
+                Some(slot) => {
+                    *slot = value;
+                    Ok(())
+                }
+                None => Self::undefined(name),
+            };
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            None => Self::undefined(name),
+        }
+    }
+
+    fn undefined<T>(name: &Token) -> Result<T, RuntimeError> {
+        RuntimeError::error(&error_code::UNDEFINED_VARIABLE, name.span, "Environment.rs", &format!("Undefined variable '{}'.", name.lexeme))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{exception::BaseException, span::Span, token::{Literal, TokenEnum}};
+
+    use super::*;
+
+    fn name(lexeme: &str) -> Token {
+        Token::new(TokenEnum::Identifier, lexeme, Literal::None, Span::new(0, lexeme.len(), 1, 1))
+    }
+
+    #[test]
+    fn define_get_and_assign() {
+        let mut environment = Environment::new();
+        environment.define("a", Value::Number(1));
+        environment.assign(&name("a"), Value::Bool(true)).unwrap();
+
+        assert_eq!(environment.get(&name("a")).unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn nested_scopes() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer.borrow_mut().define("a", Value::Number(1));
+        outer.borrow_mut().define("b", Value::Number(2));
+
+        let mut inner = Environment::with_enclosing(outer.clone());
+        inner.define("a", Value::Number(10));
+        inner.assign(&name("b"), Value::Number(20)).unwrap();
+
+        assert_eq!(inner.get(&name("a")).unwrap(), Value::Number(10));
+        assert_eq!(outer.borrow().get(&name("a")).unwrap(), Value::Number(1));
+        assert_eq!(outer.borrow().get(&name("b")).unwrap(), Value::Number(20));
+    }
+
+    #[test]
+    fn at_a_distance() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer.borrow_mut().define("a", Value::Number(1));
+
+        let mut inner = Environment::with_enclosing(outer.clone());
+        inner.define("a", Value::Number(10));
+        inner.assign_at(1, &name("a"), Value::Number(2)).unwrap();
+
+        assert_eq!(inner.get_at(0, &name("a")).unwrap(), Value::Number(10));
+        assert_eq!(inner.get_at(1, &name("a")).unwrap(), Value::Number(2));
+        assert!(inner.get_at(2, &name("a")).is_err());
+    }
+
+    #[test]
+    fn undefined() {
+        let mut environment = Environment::new();
+
+        assert_eq!(environment.get(&name("nope")).unwrap_err().message(), "Undefined variable 'nope'.");
+        assert!(environment.assign(&name("nope"), Value::None).is_err());
+    }
+}
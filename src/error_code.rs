@@ -0,0 +1,476 @@
+use std::fmt;
+
+// Every diagnostic has one of these codes so tools (and people searching the web)
+// can tell the errors apart without matching on the message, which may change.
+// Codes never change meaning: T00xx are lexical, T01xx syntax (or other errors found
+// before running), T02xx runtime errors and T03xx warnings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code)
+    }
+}
+
+pub const UNEXPECTED_CHARACTER: ErrorCode = ErrorCode {
+    code: "T0001",
+    title: "unexpected character",
+    explanation: "\
+The scanner found a character that can't start any token.
+
+    var price = 10 $ 2;
+
+Only letters, digits, `_`, the operators and punctuation of the language and
+string quotes are allowed outside of strings and comments. Remove the character
+or put it inside a string.",
+};
+
+pub const UNTERMINATED_STRING: ErrorCode = ErrorCode {
+    code: "T0002",
+    title: "unterminated string",
+    explanation: "\
+A string was opened with `\"` but the file ended before the closing `\"`.
+
+    print \"hello;
+
+Strings may span many lines, so the error points at where the string starts.
+Add the missing `\"`.",
+};
+
+pub const UNTERMINATED_COMMENT: ErrorCode = ErrorCode {
+    code: "T0003",
+    title: "unterminated block comment",
+    explanation: "\
+A block comment was opened with `/*` but never closed with `*/`.
+
+    1 + 2 /* the rest of the file is a comment
+
+Close the comment with `*/`.",
+};
+
+pub const INVALID_NUMBER: ErrorCode = ErrorCode {
+    code: "T0004",
+    title: "invalid number literal",
+    explanation: "\
+The characters scanned don't make a number the interpreter can represent.
+
+    1e400
+    0x
+    0b102
+    1__000
+
+Floats go up to about 1.8e308. A `0x`, `0o` or `0b` prefix needs at least one
+digit after it, and only the digits of that base (0-9 and a-f, 0-7, 0 and 1). A
+`_` can separate digits but must have a digit on both sides.",
+};
+
+pub const EXPECTED_EXPRESSION: ErrorCode = ErrorCode {
+    code: "T0100",
+    title: "expected expression",
+    explanation: "\
+The parser needed an expression (a literal, a grouping or an operator applied to
+expressions) but found something else.
+
+    1 + ;
+
+Every binary operator needs an operand on both sides, and a `(` must be
+followed by an expression.",
+};
+
+pub const EXPECTED_TOKEN: ErrorCode = ErrorCode {
+    code: "T0101",
+    title: "expected token",
+    explanation: "\
+The parser needed a specific token, like the `)` closing a grouping, but found
+something else.
+
+    (1 + 2
+
+The message says which token was expected. Secondary labels show the token that
+made it necessary, for example the `(` being closed.",
+};
+
+pub const INVALID_ASSIGNMENT_TARGET: ErrorCode = ErrorCode {
+    code: "T0102",
+    title: "invalid assignment target",
+    explanation: "\
+The left side of `=` isn't something a value can be stored in.
+
+    1 + a = 2;
+
+Only variables can be assigned to.",
+};
+
+pub const JUMP_OUTSIDE_LOOP: ErrorCode = ErrorCode {
+    code: "T0103",
+    title: "`break` or `continue` outside of a loop",
+    explanation: "\
+`break` and `continue` only make sense inside the body of a `while` or `for`.
+
+    if (done) break;
+
+Move the statement inside a loop, or use an `if` to skip the rest of the code.",
+};
+
+pub const UNDEFINED_LABEL: ErrorCode = ErrorCode {
+    code: "T0104",
+    title: "undefined loop label",
+    explanation: "\
+`break` or `continue` names a label that no enclosing loop has.
+
+    outer: while (true) {
+        while (true) break outter;
+    }
+
+Labels are written before a loop (`name: while ...`) and can only be used
+inside that loop, check the spelling.",
+};
+
+pub const TOO_MANY_ARGUMENTS: ErrorCode = ErrorCode {
+    code: "T0105",
+    title: "too many arguments or parameters",
+    explanation: "\
+A call passes, or a function declares, more than 255 arguments.
+
+Group related values in fewer parameters instead.",
+};
+
+pub const READ_IN_OWN_INITIALIZER: ErrorCode = ErrorCode {
+    code: "T0106",
+    title: "local variable read in its own initializer",
+    explanation: "\
+A local variable is used in the expression that gives it its first value.
+
+    var a = 1;
+    {
+        var a = a + 1;
+    }
+
+Inside the block `a` already means the new variable, which has no value yet.
+Give the inner variable another name.",
+};
+
+pub const DUPLICATE_DECLARATION: ErrorCode = ErrorCode {
+    code: "T0107",
+    title: "name declared twice in the same scope",
+    explanation: "\
+A block or function declares the same name twice.
+
+    fun area(width, height) {
+        var width = 10;
+    }
+
+The second declaration would hide the first for the rest of the scope, which is
+almost always a mistake. Assign to the variable instead, or pick another name.
+Global variables can be declared again, so scripts and the prompt can redefine them.",
+};
+
+pub const RETURN_OUTSIDE_FUNCTION: ErrorCode = ErrorCode {
+    code: "T0108",
+    title: "`return` outside of a function",
+    explanation: "\
+`return` is only allowed inside a function body.
+
+    if (done) return;
+
+Wrap the code in a function, or use `if`/`else` to skip the rest of the script.",
+};
+
+pub const RETURN_VALUE_FROM_INITIALIZER: ErrorCode = ErrorCode {
+    code: "T0109",
+    title: "`init` returns a value",
+    explanation: "\
+An `init` method returns something with `return value;`.
+
+    class Point {
+        init(x) { return x; }
+    }
+
+Calling a class always gives the new instance, a value returned by `init` would
+be lost. A bare `return;` is fine to leave `init` early.",
+};
+
+pub const THIS_OUTSIDE_CLASS: ErrorCode = ErrorCode {
+    code: "T0110",
+    title: "`this` outside of a class",
+    explanation: "\
+`this` was used outside of a method.
+
+    fun area() { return this.width * this.height; }
+
+`this` is the instance a method was called on, so it only exists inside the
+methods of a class.",
+};
+
+pub const INHERIT_FROM_SELF: ErrorCode = ErrorCode {
+    code: "T0111",
+    title: "class inherits from itself",
+    explanation: "\
+A class names itself as its superclass.
+
+    class Node < Node {}
+
+Inherit from another class, or leave out the `< Name`.",
+};
+
+pub const SUPER_OUTSIDE_SUBCLASS: ErrorCode = ErrorCode {
+    code: "T0112",
+    title: "`super` outside of a subclass",
+    explanation: "\
+`super` was used outside of a method, or in a class without a superclass.
+
+    class Point {
+        init() { super.init(); }
+    }
+
+`super.method` calls the superclass version of a method, so the class needs one:
+`class Point < Shape`.",
+};
+
+pub const NESTING_TOO_DEEP: ErrorCode = ErrorCode {
+    code: "T0113",
+    title: "nesting too deep",
+    explanation: "\
+Expressions, blocks or statements are nested inside each other too many times.
+
+    print ((((((((((((((1))))))))))))));  // but hundreds of levels deep
+    print 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1;  // but hundreds of terms long
+
+The parser allows 255 levels. Every operator in a chain and every call or `.`
+in `a.b().c` counts as one. Split the code into functions or intermediate
+variables to make it flatter.",
+};
+
+pub const OPERAND_NOT_A_NUMBER: ErrorCode = ErrorCode {
+    code: "T0200",
+    title: "operand must be a number",
+    explanation: "\
+An arithmetic or comparison operator was applied to something that isn't a number.
+
+    -\"ten\"
+    \"a\" < 1
+
+The operators `-`, `*`, `/`, `%`, `>`, `>=`, `<` and `<=` only work on numbers.
+The error points at the operand with the wrong type.",
+};
+
+pub const INVALID_PLUS_OPERANDS: ErrorCode = ErrorCode {
+    code: "T0201",
+    title: "invalid operands for `+`",
+    explanation: "\
+`+` adds two numbers or concatenates two strings, mixing them is an error.
+
+    \"total: \" + 10
+
+Both operands must be numbers or both must be strings.",
+};
+
+pub const UNSUPPORTED_OPERATOR: ErrorCode = ErrorCode {
+    code: "T0202",
+    title: "unsupported operator",
+    explanation: "\
+The interpreter doesn't know how to evaluate this operator in this position.
+This usually means the parser accepted something the interpreter doesn't
+implement yet, please report it.",
+};
+
+pub const DIVISION_BY_ZERO: ErrorCode = ErrorCode {
+    code: "T0203",
+    title: "division by zero",
+    explanation: "\
+The right side of `/` or `%` evaluated to zero.
+
+    10 / (5 - 5)
+    10 % 0.0
+
+This is an error for floats too, and in every arithmetic mode. Check the divisor
+before dividing.",
+};
+
+pub const INTEGER_OVERFLOW: ErrorCode = ErrorCode {
+    code: "T0204",
+    title: "integer overflow",
+    explanation: "\
+The result of an arithmetic operation doesn't fit in a 64-bit integer.
+
+    9223372036854775807 + 1
+
+The interpreter switches to a bignum by default, so this only happens when a
+program that embeds it asked for `Arithmetic::Checked`, where integers go from
+-9223372036854775808 to 9223372036854775807. Wrapping around and saturating are
+also available, see `Interpreter::arithmetic`.",
+};
+
+pub const OUTPUT_FAILED: ErrorCode = ErrorCode {
+    code: "T0205",
+    title: "output failed",
+    explanation: "\
+`print` couldn't write its value, for example because stdout was closed
+(`totx script.tx | head -1` closes it after the first line).
+
+The message has the error given by the operating system.",
+};
+
+pub const UNDEFINED_VARIABLE: ErrorCode = ErrorCode {
+    code: "T0206",
+    title: "undefined variable",
+    explanation: "\
+A variable was read or assigned before being declared with `var`.
+
+    print total;
+    var total = 10;
+
+Declare the variable first. Assigning doesn't declare it either, `total = 1;`
+fails the same way when there's no `var total`.",
+};
+
+pub const NOT_CALLABLE: ErrorCode = ErrorCode {
+    code: "T0207",
+    title: "value is not callable",
+    explanation: "\
+Something that isn't a function was called.
+
+    var name = \"totx\";
+    name();
+
+Only functions can be called.",
+};
+
+pub const WRONG_ARITY: ErrorCode = ErrorCode {
+    code: "T0208",
+    title: "wrong number of arguments",
+    explanation: "\
+A function was called with a different number of arguments than it declares.
+
+    fun add(a, b) { return a + b; }
+    add(1);
+
+Pass exactly one argument for each parameter.",
+};
+
+pub const STACK_OVERFLOW: ErrorCode = ErrorCode {
+    code: "T0209",
+    title: "stack overflow",
+    explanation: "\
+Too many calls were nested, usually a recursive function that never stops.
+
+    fun forever() { forever(); }
+
+Check the condition that should end the recursion.",
+};
+
+pub const UNUSED_VARIABLE: ErrorCode = ErrorCode {
+    code: "T0300",
+    title: "unused local variable",
+    explanation: "\
+A local variable or function is declared but never read.
+
+    fun total(items) {
+        var count = 0;
+        return 10;
+    }
+
+It's a warning, the script still runs. Remove the declaration, or start the name
+with `_` when it's unused on purpose. Globals and parameters are never reported.",
+};
+
+pub const NOT_AN_INSTANCE: ErrorCode = ErrorCode {
+    code: "T0210",
+    title: "only instances have properties",
+    explanation: "\
+A property was read or set with `.` on something that isn't an instance of a class.
+
+    var name = \"totx\";
+    print name.length;
+
+Only instances created by calling a class have fields and methods.",
+};
+
+pub const UNDEFINED_PROPERTY: ErrorCode = ErrorCode {
+    code: "T0211",
+    title: "undefined property",
+    explanation: "\
+An instance has no field and its class no method with the name read.
+
+    class Point {}
+    var point = Point();
+    print point.x;
+
+Fields exist once something is assigned to them, `point.x = 1;`, usually in `init`.",
+};
+
+pub const SUPERCLASS_NOT_A_CLASS: ErrorCode = ErrorCode {
+    code: "T0212",
+    title: "superclass is not a class",
+    explanation: "\
+The name after `<` in a class declaration holds something that isn't a class.
+
+    var Shape = \"shape\";
+    class Point < Shape {}
+
+Classes can only inherit from other classes.",
+};
+
+pub const REGISTRY: &[ErrorCode] = &[
+    UNEXPECTED_CHARACTER,
+    UNTERMINATED_STRING,
+    UNTERMINATED_COMMENT,
+    INVALID_NUMBER,
+    EXPECTED_EXPRESSION,
+    EXPECTED_TOKEN,
+    INVALID_ASSIGNMENT_TARGET,
+    JUMP_OUTSIDE_LOOP,
+    UNDEFINED_LABEL,
+    TOO_MANY_ARGUMENTS,
+    READ_IN_OWN_INITIALIZER,
+    DUPLICATE_DECLARATION,
+    RETURN_OUTSIDE_FUNCTION,
+    RETURN_VALUE_FROM_INITIALIZER,
+    THIS_OUTSIDE_CLASS,
+    INHERIT_FROM_SELF,
+    SUPER_OUTSIDE_SUBCLASS,
+    NESTING_TOO_DEEP,
+    OPERAND_NOT_A_NUMBER,
+    INVALID_PLUS_OPERANDS,
+    UNSUPPORTED_OPERATOR,
+    DIVISION_BY_ZERO,
+    INTEGER_OVERFLOW,
+    OUTPUT_FAILED,
+    UNDEFINED_VARIABLE,
+    NOT_CALLABLE,
+    WRONG_ARITY,
+    STACK_OVERFLOW,
+    NOT_AN_INSTANCE,
+    UNDEFINED_PROPERTY,
+    SUPERCLASS_NOT_A_CLASS,
+    UNUSED_VARIABLE,
+];
+
+// Case doesn't matter, `t0001` finds T0001.
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    REGISTRY.iter().find(|error| error.code.eq_ignore_ascii_case(code.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_unique() {
+        for (index, error) in REGISTRY.iter().enumerate() {
+            assert!(REGISTRY[index + 1..].iter().all(|other| other.code != error.code), "{} is repeated", error);
+        }
+    }
+
+    #[test]
+    fn finds_codes() {
+        assert_eq!(lookup("T0002"), Some(&UNTERMINATED_STRING));
+        assert_eq!(lookup("t0100"), Some(&EXPECTED_EXPRESSION));
+        assert_eq!(lookup("T9999"), None);
+    }
+}
@@ -1,82 +1,256 @@
 use core::fmt;
 
+use crate::{diagnostic::Diagnostic, error_code::ErrorCode, span::Span};
+
+// What every error of the pipeline can tell about itself. Each phase has its own type
+// (`LexError`, `ParseError`, `RuntimeError`) so callers can tell a script that didn't
+// compile from one that failed while running, but they all render the same way.
+pub trait BaseException: std::error::Error {
+    fn code(&self) -> &'static ErrorCode;
+
+    fn span(&self) -> Span;
+
+    fn message(&self) -> &str;
+
+    fn diagnostic(&self) -> Diagnostic;
+
+    fn line(&self) -> u32 {
+        self.span().line
+    }
+
+    fn column(&self) -> u32 {
+        self.span().column
+    }
+}
+
+// The data shared by the error types, they only wrap it.
 #[derive(Debug, Clone)]
-pub struct Exception {
-    line: u32,
+pub(crate) struct Exception {
+    code: &'static ErrorCode,
+    span: Span,
     message: String,
-    where_r: String
+    where_r: String,
+    // Extra context shown by the diagnostics renderer.
+    extra: Vec<Extra>,
 }
 
-pub type Result<T> = std::result::Result<T, Exception>;
+#[derive(Debug, Clone)]
+enum Extra {
+    Secondary(Span, String),
+    Note(String),
+    Help(String),
+}
 
 impl fmt::Display for Exception {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[Line - {} ] \n Error {} : {}", self.line, self.where_r, self.message)
+        write!(f, "[Line - {}, Column - {} ] \n Error[{}] {} : {}", self.span.line, self.span.column, self.code, self.where_r, self.message)
     }
 }
 
 impl Exception {
-    pub fn new(line: u32, where_r: &str, message: &str) -> Self {
+    pub fn new(code: &'static ErrorCode, span: Span, where_r: &str, message: &str) -> Self {
         Self {
-            line: line,
+            code,
+            span,
             message: message.to_string(),
-            where_r: where_r.to_string()
+            where_r: where_r.to_string(),
+            extra: Vec::new(),
+        }
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: &str) -> Self {
+        self.extra.push(Extra::Secondary(span, message.to_string()));
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.extra.push(Extra::Note(note.to_string()));
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.extra.push(Extra::Help(help.to_string()));
+        self
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(&self.message)
+            .with_code(self.code.code)
+            .with_label(self.span, "");
+
+        self.extra.iter().fold(diagnostic, |diagnostic, extra| match extra {
+            Extra::Secondary(span, message) => diagnostic.with_secondary(*span, message),
+            Extra::Note(note) => diagnostic.with_note(note),
+            Extra::Help(help) => diagnostic.with_help(help),
+        })
+    }
+
+}
+
+macro_rules! exception_type {
+    ($name:ident) => {
+        #[derive(Debug, Clone)]
+        pub struct $name(Exception);
+
+        impl $name {
+            pub fn new(code: &'static ErrorCode, span: Span, where_r: &str, message: &str) -> Self {
+                Self(Exception::new(code, span, where_r, message))
+            }
+
+            pub fn error<T>(code: &'static ErrorCode, span: Span, where_r: &str, message: &str) -> Result<T, Self> {
+                Err(Self::new(code, span, where_r, message))
+            }
+
+            pub fn with_secondary(self, span: Span, message: &str) -> Self {
+                Self(self.0.with_secondary(span, message))
+            }
+
+            pub fn with_note(self, note: &str) -> Self {
+                Self(self.0.with_note(note))
+            }
+
+            pub fn with_help(self, help: &str) -> Self {
+                Self(self.0.with_help(help))
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl std::error::Error for $name {}
+
+        impl BaseException for $name {
+            fn code(&self) -> &'static ErrorCode {
+                self.0.code
+            }
+
+            fn span(&self) -> Span {
+                self.0.span
+            }
+
+            fn message(&self) -> &str {
+                &self.0.message
+            }
+
+            fn diagnostic(&self) -> Diagnostic {
+                self.0.diagnostic()
+            }
         }
+    };
+}
+
+// A character or literal the scanner can't make a token of.
+exception_type!(LexError);
+// Tokens that don't follow the grammar.
+exception_type!(ParseError);
+// Declarations and uses of names that parse but can't be right, like `return` outside a function.
+exception_type!(ResolveError);
+// Something that went wrong while running a script that parsed fine.
+exception_type!(RuntimeError);
+
+// Any failure of running a script from start to end, see `totx::run`.
+#[derive(Debug, Clone)]
+pub enum Error {
+    Lex(Vec<LexError>),
+    Parse(Vec<ParseError>),
+    Resolve(Vec<ResolveError>),
+    Runtime(RuntimeError),
+}
+
+impl Error {
+    // Scanning, parsing and resolving errors mean the script never started running.
+    pub fn is_compile_time(&self) -> bool {
+        !matches!(self, Error::Runtime(_))
     }
 
-    pub fn error<T>(line: u32, where_r: &str, message: &str) -> Result<T> {
-        Err(Exception::new(line, where_r, message))
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            Error::Lex(errors) => errors.iter().map(|error| error.diagnostic()).collect(),
+            Error::Parse(errors) => errors.iter().map(|error| error.diagnostic()).collect(),
+            Error::Resolve(errors) => errors.iter().map(|error| error.diagnostic()).collect(),
+            Error::Runtime(error) => vec![error.diagnostic()],
+        }
     }
+}
 
-    pub fn error_panic<T>(line: u32, where_r: &str, message: &str) -> Result<T> {
-        panic!("[Line - {} ] \n Error {} : {}", line, where_r, message);
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Lex(errors) => write_all(f, errors),
+            Error::Parse(errors) => write_all(f, errors),
+            Error::Resolve(errors) => write_all(f, errors),
+            Error::Runtime(error) => write!(f, "{}", error),
+        }
     }
 }
 
+impl std::error::Error for Error {}
+
+fn write_all<E: fmt::Display>(f: &mut fmt::Formatter<'_>, errors: &[E]) -> fmt::Result {
+    for (index, error) in errors.iter().enumerate() {
+        if index > 0 {
+            writeln!(f)?;
+        }
+        write!(f, "{}", error)?;
+    }
+
+    Ok(())
+}
+
+impl From<Vec<LexError>> for Error {
+    fn from(errors: Vec<LexError>) -> Self {
+        Error::Lex(errors)
+    }
+}
 
-// Thinking to do something like this, look I trying taking this from C#. It's this good ?
-// With that problably I can implementing many exception, where will be some erros return.
-// And for the runtime I can implement with the panic
-
-// pub type Result<T, E: BaseException> = std::result::Result<T, E>;
-
-// pub trait BaseException: fmt::Debug + fmt::Display {
-//     fn error<T, E>(line: u32, where_r: &str, message: &str) -> Result<T, E>;
-
-//     fn new(line: u32, where_r: &str, message: &str) -> Self;
-// }
-
-// #[derive(Debug, Clone)]
-// pub struct Exception {
-//     line: u32,
-//     message: String,
-//     where_r: String
-// }
-
-// impl BaseException for Exception {
-//     fn new(line: u32, where_r: &str, message: &str) -> Self {
-//         Self {
-//             line: line,
-//             message: message.to_string(),
-//             where_r: where_r.to_string()
-//         }
-//     }
-
-//     fn error<T, E>(line: u32, where_r: &str, message: &str) -> Result<T, Exception> {
-
-//     }
-// }
-
-// impl BaseException for ExceptionRuntime {
-//     fn new(line: u32, where_r: &str, message: &str) -> Self {
-//         Self {
-//             line: line,
-//             message: message.to_string(),
-//             where_r: where_r.to_string()
-//         }
-//     }
-
-//     fn error<T, E>(line: u32, where_r: &str, message: &str) -> Result<T, Exception> {
-        // panic!();
-//     }
-// }
+impl From<Vec<ParseError>> for Error {
+    fn from(errors: Vec<ParseError>) -> Self {
+        Error::Parse(errors)
+    }
+}
+
+impl From<Vec<ResolveError>> for Error {
+    fn from(errors: Vec<ResolveError>) -> Self {
+        Error::Resolve(errors)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Self {
+        Error::Runtime(error)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{error_code, interpreter::Interpreter};
+
+    use super::*;
+
+    #[test]
+    fn tells_the_phases_apart() {
+        let mut interpreter = Interpreter::with_output(Vec::new());
+        let lex = crate::run("1 # 2", &mut interpreter).unwrap_err();
+        let parse = crate::run("(1 + 2; 3 +", &mut interpreter).unwrap_err();
+        let resolve = crate::run("return 1;", &mut interpreter).unwrap_err();
+        let runtime = crate::run("1 + \"a\";", &mut interpreter).unwrap_err();
+
+        assert!(matches!(lex, Error::Lex(_)) && lex.is_compile_time());
+        assert!(matches!(parse, Error::Parse(_)) && parse.is_compile_time());
+        assert_eq!(parse.diagnostics().len(), 2);
+        assert!(matches!(resolve, Error::Resolve(_)) && resolve.is_compile_time());
+        assert!(matches!(runtime, Error::Runtime(_)) && !runtime.is_compile_time());
+        assert_eq!(runtime.diagnostics()[0].code, Some(error_code::INVALID_PLUS_OPERANDS.code));
+    }
+
+    #[test]
+    fn is_a_std_error() {
+        let error: Box<dyn std::error::Error> = Box::new(crate::run("-\"a\";", &mut Interpreter::with_output(Vec::new())).unwrap_err());
+
+        assert!(error.to_string().contains("must be a number"));
+    }
+}
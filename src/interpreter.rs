@@ -1,54 +1,454 @@
-use crate::{ast::Expression, exception::{self, Exception}, token::{Literal, TokenEnum}};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    io::{self, Write},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+use num_bigint::{BigInt, Sign};
+use num_traits::{ToPrimitive, Zero};
 
+use crate::{
+    ast::{Expression, Stmt},
+    environment::Environment,
+    error_code,
+    exception::RuntimeError,
+    span::Span,
+    token::{Literal, Token, TokenEnum},
+    value::{compare_integer_float, Class, Function, Instance, NativeFunction, Value},
+    Program,
+};
+
+// Every call nests a few Rust frames, past this many calls the script fails instead
+// of the interpreter overflowing its own stack.
+const MAX_CALL_DEPTH: usize = 1000;
+
+// Stack the interpreter needs to reach MAX_CALL_DEPTH, even in debug builds. Run it
+// on a thread with at least this much, the main thread usually has less.
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+
+// What integer arithmetic does when the result doesn't fit in an i64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arithmetic {
+    // Switches to a bignum, and back to an i64 once the value fits again.
+    #[default]
+    Bignum,
+    // A runtime error pointing at the operator.
+    Checked,
+    // Wraps around, like two's complement hardware: `9223372036854775807 + 1` is the smallest i64.
+    Wrapping,
+    // Sticks to the largest or smallest i64.
+    Saturating,
+}
+
+// `print` writes to `out`, stdout unless the embedder gives us something else.
 #[derive(Debug, Clone)]
-pub struct Interpreter;
+pub struct Interpreter<W: Write = io::Stdout> {
+    out: W,
+    arithmetic: Arithmetic,
+    // The outermost scope, where the native functions are.
+    globals: Rc<RefCell<Environment>>,
+    // The innermost scope of whatever is running right now.
+    environment: Rc<RefCell<Environment>>,
+    // How many calls are running right now.
+    depth: usize,
+}
+
+type EvaluateResult<T> = Result<T, RuntimeError>;
 
-type EvaluateResult<T> = exception::Result<T>;
+// How a statement finished. Anything but Normal unwinds through the enclosing
+// statements until the loop it's meant for handles it.
+#[derive(Debug, Clone, PartialEq)]
+enum Flow {
+    // Carries the value of expression statements, for the prompt.
+    Normal(Option<Value>),
+    Break(Option<String>),
+    Continue(Option<String>),
+    Return(Value),
+}
 
 impl Interpreter {
-    pub fn interpret(&self, expr: &Expression) -> EvaluateResult<Literal> {
-        // TODO: Improve that with error
-        self.evaluate(expr)
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> Interpreter<W> {
+    pub fn with_output(out: W) -> Self {
+        let globals = Rc::new(RefCell::new(globals()));
+        Self { out, arithmetic: Arithmetic::default(), environment: globals.clone(), globals, depth: 0 }
+    }
+
+    // Bignums unless told otherwise. Division and modulo by zero are errors in every mode.
+    pub fn arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.arithmetic = arithmetic;
+        self
+    }
+
+    // Forgets every variable defined so far, keeping the output.
+    pub fn reset(&mut self) {
+        self.globals = Rc::new(RefCell::new(globals()));
+        self.environment = self.globals.clone();
+    }
+
+    pub fn output(&self) -> &W {
+        &self.out
+    }
+
+    pub fn output_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    // Runs the statements in order, stopping at the first runtime error. When the last
+    // statement is an expression its value comes back, the prompt shows it. Takes a
+    // compiled program because it relies on the depths the resolver filled in.
+    pub fn interpret(&mut self, program: &Program) -> EvaluateResult<Option<Value>> {
+        let mut value = None;
+
+        for statement in program.statements() {
+            // The parser doesn't let break and continue get here outside a loop.
+            match self.execute(statement)? {
+                Flow::Normal(result) => value = result,
+                // A return outside any function ends the script.
+                Flow::Return(_) => return Ok(None),
+                _ => (),
+            }
+        }
+
+        Ok(value)
+    }
+
+    fn execute(&mut self, statement: &Stmt) -> EvaluateResult<Flow> {
+        match statement {
+            Stmt::Expression { expression, .. } => Ok(Flow::Normal(Some(self.evaluate(expression)?))),
+            Stmt::Print { expression, span } => {
+                let value = self.evaluate(expression)?;
+                if let Err(error) = writeln!(self.out, "{}", value) {
+                    return RuntimeError::error(&error_code::OUTPUT_FAILED, *span, "Interpreter.rs", &format!("Can't write the output: {}.", error));
+                }
+                Ok(Flow::Normal(None))
+            }
+            Stmt::Var { name, initializer, .. } => {
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::None,
+                };
+                self.environment.borrow_mut().define(&name.lexeme, value);
+                Ok(Flow::Normal(None))
+            }
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                if is_truthy(self.evaluate(condition)?) {
+                    return self.execute(then_branch).map(unwinding);
+                } else if let Some(else_branch) = else_branch {
+                    return self.execute(else_branch).map(unwinding);
+                }
+                Ok(Flow::Normal(None))
+            }
+            Stmt::While { label, condition, body, increment, .. } => {
+                let label = label.as_ref().map(|label| label.lexeme.as_str());
+
+                while is_truthy(self.evaluate(condition)?) {
+                    match self.execute(body)? {
+                        Flow::Break(target) if targets(&target, label) => break,
+                        Flow::Normal(_) => (),
+                        Flow::Continue(target) if targets(&target, label) => (),
+                        // Meant for a loop further out.
+                        flow => return Ok(flow),
+                    }
+
+                    if let Some(increment) = increment {
+                        self.evaluate(increment)?;
+                    }
+                }
+                Ok(Flow::Normal(None))
+            }
+            Stmt::Function { declaration } => {
+                let function = Function { declaration: declaration.clone(), closure: self.environment.clone(), is_initializer: false };
+                self.environment.borrow_mut().define(&declaration.name.lexeme, Value::Function(Rc::new(function)));
+                Ok(Flow::Normal(None))
+            }
+            // With a superclass the methods close over an extra scope holding it as `super`.
+            Stmt::Class { name, superclass, methods, .. } => {
+                let superclass = match superclass {
+                    Some(expression) => match self.evaluate(expression)? {
+                        Value::Class(class) => Some(class),
+                        other => {
+                            let error = RuntimeError::new(&error_code::SUPERCLASS_NOT_A_CLASS, expression.span(), "Interpreter.rs", "Superclass must be a class.")
+                                .with_secondary(expression.span(), &format!("this has type {}", other.type_name()));
+                            return Err(error);
+                        }
+                    },
+                    None => None,
+                };
+
+                let enclosing = self.environment.clone();
+                if let Some(superclass) = &superclass {
+                    let mut environment = Environment::with_enclosing(enclosing.clone());
+                    environment.define("super", Value::Class(superclass.clone()));
+                    self.environment = Rc::new(RefCell::new(environment));
+                }
+
+                let methods = methods
+                    .iter()
+                    .map(|declaration| {
+                        let is_initializer = declaration.name.lexeme == "init";
+                        let method = Function { declaration: declaration.clone(), closure: self.environment.clone(), is_initializer };
+                        (declaration.name.lexeme.clone(), Rc::new(method))
+                    })
+                    .collect();
+
+                self.environment = enclosing;
+                let class = Class { name: name.lexeme.clone(), superclass, methods };
+                self.environment.borrow_mut().define(&name.lexeme, Value::Class(Rc::new(class)));
+                Ok(Flow::Normal(None))
+            }
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::None,
+                };
+                Ok(Flow::Return(value))
+            }
+            Stmt::Break { label, .. } => Ok(Flow::Break(label.as_ref().map(|label| label.lexeme.clone()))),
+            Stmt::Continue { label, .. } => Ok(Flow::Continue(label.as_ref().map(|label| label.lexeme.clone()))),
+            Stmt::Block { statements, .. } => {
+                let environment = Environment::with_enclosing(self.environment.clone());
+                self.execute_block(statements, Rc::new(RefCell::new(environment)))
+            }
+        }
+    }
+
+    // Runs the statements inside `environment`, putting the previous one back afterwards
+    // even when a statement fails. Stops early when a statement breaks out.
+    fn execute_block(&mut self, statements: &[Stmt], environment: Rc<RefCell<Environment>>) -> EvaluateResult<Flow> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+
+        let mut result = Ok(Flow::Normal(None));
+        for statement in statements {
+            result = self.execute(statement).map(unwinding);
+            if !matches!(result, Ok(Flow::Normal(_))) {
+                break;
+            }
+        }
+
+        self.environment = previous;
+        result
+    }
+
+    // The arguments were already checked against the arity.
+    fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> EvaluateResult<Value> {
+        match callee {
+            Value::Function(function) => self.call_function(function, arguments),
+            Value::Native(native) => Ok((native.function)(&arguments)),
+            Value::Class(class) => {
+                let instance = Instance { class: class.clone(), fields: HashMap::new() };
+                let instance = Value::Instance(Rc::new(RefCell::new(instance)));
+
+                if let Some(init) = class.find_method("init") {
+                    self.call_function(&init.bind(instance.clone()), arguments)?;
+                }
+                Ok(instance)
+            }
+            _ => unreachable!("only functions and classes have an arity"),
+        }
+    }
+
+    fn call_function(&mut self, function: &Function, arguments: Vec<Value>) -> EvaluateResult<Value> {
+        let mut environment = Environment::with_enclosing(function.closure.clone());
+        for (param, argument) in function.declaration.params.iter().zip(arguments) {
+            environment.define(&param.lexeme, argument);
+        }
+
+        let value = match self.execute_block(&function.declaration.body, Rc::new(RefCell::new(environment)))? {
+            Flow::Return(value) => value,
+            _ => Value::None,
+        };
+
+        if function.is_initializer {
+            return Ok(function.closure.borrow().get_own("this").unwrap_or(Value::None));
+        }
+        Ok(value)
+    }
+
+    fn look_up_variable(&self, name: &Token, depth: Option<usize>) -> EvaluateResult<Value> {
+        match depth {
+            Some(depth) => self.environment.borrow().get_at(depth, name),
+            None => self.globals.borrow().get(name),
+        }
     }
 
     // We eargely produced the runtime value way back during scanning and stuffed it in the token. The parser took that value and stuck it in the literal tree node, so to evaluate a literal, we simply pull it back out.
-    fn evaluate(&self, expr: &Expression) -> EvaluateResult<Literal> {
+    fn evaluate(&mut self, expr: &Expression) -> EvaluateResult<Value> {
         let result = match expr {
-            Expression::Literal { value } => value.clone(),
-            Expression::Grouping { expression } => self.evaluate(expression)?,
-            Expression::Unary { operator, right } => match operator.token_type {
-                TokenEnum::Minus => Literal::Number(check_number(self.evaluate(right)?)?),
-                TokenEnum::Bang => Literal::Bool(!is_truthy(self.evaluate(right)?)),
-                _ => return Exception::error(operator.line, "Interpreter.rs", "message")
+            Expression::Literal { value, .. } => Value::from(value.clone()),
+            Expression::Variable { name, depth, .. } => self.look_up_variable(name, *depth)?,
+            Expression::This { keyword, depth, .. } => self.look_up_variable(keyword, *depth)?,
+            // `super` is where the resolver says, `this` is always in the scope just inside it.
+            Expression::Super { keyword, method, depth, .. } => {
+                let depth = depth.expect("the resolver only allows super inside methods");
+                let superclass = self.environment.borrow().get_at(depth, keyword)?;
+                let this = Token::new(TokenEnum::This, "this", Literal::None, keyword.span);
+                let object = self.environment.borrow().get_at(depth - 1, &this)?;
+
+                let Value::Class(superclass) = superclass else {
+                    unreachable!("super is always a class");
+                };
+                match superclass.find_method(&method.lexeme) {
+                    Some(found) => Value::Function(Rc::new(found.bind(object))),
+                    None => {
+                        let error = RuntimeError::new(&error_code::UNDEFINED_PROPERTY, method.span, "Interpreter.rs", &format!("Undefined property '{}'.", method.lexeme))
+                            .with_secondary(keyword.span, &format!("this is {}", superclass.name));
+                        return Err(error);
+                    }
+                }
+            },
+            // Fields shadow methods with the same name.
+            Expression::Get { object, name, .. } => {
+                let object_span = object.span();
+                let object = self.evaluate(object)?;
+                let instance = instance(&object, object_span, "Only instances have properties.")?;
+
+                let field = instance.borrow().fields.get(&name.lexeme).cloned();
+                let method = instance.borrow().class.find_method(&name.lexeme);
+                match (field, method) {
+                    (Some(field), _) => field,
+                    (None, Some(method)) => Value::Function(Rc::new(method.bind(object.clone()))),
+                    (None, None) => {
+                        let error = RuntimeError::new(&error_code::UNDEFINED_PROPERTY, name.span, "Interpreter.rs", &format!("Undefined property '{}'.", name.lexeme))
+                            .with_secondary(object_span, &format!("this is a {}", object));
+                        return Err(error);
+                    }
+                }
+            },
+            Expression::Set { object, name, value, .. } => {
+                let object_span = object.span();
+                let object = self.evaluate(object)?;
+                let instance = instance(&object, object_span, "Only instances have fields.")?;
+
+                let value = self.evaluate(value)?;
+                instance.borrow_mut().fields.insert(name.lexeme.clone(), value.clone());
+                value
+            },
+            Expression::Assign { name, value, depth, .. } => {
+                let value = self.evaluate(value)?;
+                match depth {
+                    Some(depth) => self.environment.borrow_mut().assign_at(*depth, name, value.clone())?,
+                    None => self.globals.borrow_mut().assign(name, value.clone())?,
+                }
+                value
+            },
+            Expression::Grouping { expression, .. } => self.evaluate(expression)?,
+            Expression::Comma { left, right, .. } => {
+                self.evaluate(left)?;
+                self.evaluate(right)?
+            },
+            Expression::Conditional { condition, then_branch, else_branch, .. } => {
+                if is_truthy(self.evaluate(condition)?) {
+                    self.evaluate(then_branch)?
+                } else {
+                    self.evaluate(else_branch)?
+                }
+            },
+            Expression::Call { callee, paren, arguments, .. } => {
+                let callee_span = callee.span();
+                let callee = self.evaluate(callee)?;
+
+                let mut values = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    values.push(self.evaluate(argument)?);
+                }
+
+                let Some(arity) = callee.arity() else {
+                    let error = RuntimeError::new(&error_code::NOT_CALLABLE, paren.span, "Interpreter.rs", "Can only call functions.")
+                        .with_secondary(callee_span, &format!("this has type {}", callee.type_name()));
+                    return Err(error);
+                };
+                if arity != values.len() {
+                    let error = RuntimeError::new(&error_code::WRONG_ARITY, paren.span, "Interpreter.rs", &format!("Expected {} arguments but got {}.", arity, values.len()))
+                        .with_secondary(callee_span, &format!("this is {}", callee));
+                    return Err(error);
+                }
+                if self.depth >= MAX_CALL_DEPTH {
+                    return RuntimeError::error(&error_code::STACK_OVERFLOW, paren.span, "Interpreter.rs", &format!("Stack overflow, more than {} nested calls.", MAX_CALL_DEPTH));
+                }
+
+                self.depth += 1;
+                let result = self.call(&callee, values);
+                self.depth -= 1;
+                result?
+            },
+            // Gives back the operand that decided the result, not a bool: `null or "x"` is "x".
+            Expression::Logical { operator, left, right, .. } => {
+                let left = self.evaluate(left)?;
+                let decided = match operator.token_type {
+                    TokenEnum::Or => is_truthy(left.clone()),
+                    _ => !is_truthy(left.clone()),
+                };
+
+                if decided {
+                    left
+                } else {
+                    self.evaluate(right)?
+                }
+            },
+            Expression::Unary { operator, right, .. } => match operator.token_type {
+                TokenEnum::Minus => match check_number(self.evaluate(right)?, right.span())? {
+                    Value::Float(value) => Value::Float(-value),
+                    // Same as 0 - value, negating the smallest i64 overflows.
+                    Value::Number(value) => integers(self.arithmetic, operator, 0, value)?,
+                    Value::Big(value) => fit(self.arithmetic, operator, -value)?,
+                    _ => unreachable!("checked to be a number"),
+                },
+                TokenEnum::Bang => Value::Bool(!is_truthy(self.evaluate(right)?)),
+                _ => return RuntimeError::error(&error_code::UNSUPPORTED_OPERATOR, operator.span, "Interpreter.rs", &format!("Unsupported unary operator '{}'.", operator.lexeme))
             },
             Expression::Binary {
                 operator,
                 left,
-                right
+                right,
+                ..
             } => {
-                let right = self.evaluate(right)?;
+                let (left_span, right_span) = (left.span(), right.span());
                 let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+
                 match operator.token_type {
-                    TokenEnum::Minus => Literal::Number(check_number(left)? - check_number(right)?),
-                    TokenEnum::Slash => Literal::Number(check_number(left)? / check_number(right)?),
-                    TokenEnum::Star => Literal::Number(check_number(left)? * check_number(right)?),
+                    TokenEnum::Minus | TokenEnum::Slash | TokenEnum::Star | TokenEnum::Percent => {
+                        arithmetic(self.arithmetic, operator, numbers(left, left_span, right, right_span)?, right_span)?
+                    }
                     TokenEnum::Plus => {
                         match (left, right) {
-                            // (Literal::String(left), Literal::Number(right)) => Literal::String(left + &right.to_string()), // Just because the book send the challenge
-                            // (Literal::Number(left), Literal::String(right)) => Literal::String(left.to_string() + &right), // Just because the book send the challenge
-                            (Literal::Number(left), Literal::Number(right)) => Literal::Number(left + right),
-                            (Literal::String(left), Literal::String(right)) => Literal::String(left + &right),
-                            _ => return Exception::error(operator.line, "Interpreter.rs", "Must be all string or number for PLUS (+)")
+                            // (Value::String(left), Value::Number(right)) => Value::String(left + &right.to_string()), // Just because the book send the challenge
+                            // (Value::Number(left), Value::String(right)) => Value::String(left.to_string() + &right), // Just because the book send the challenge
+                            (left @ (Value::Number(_) | Value::Big(_) | Value::Float(_)), right @ (Value::Number(_) | Value::Big(_) | Value::Float(_))) => {
+                                arithmetic(self.arithmetic, operator, numbers(left, left_span, right, right_span)?, right_span)?
+                            }
+                            (Value::String(left), Value::String(right)) => Value::String(left + &right),
+                            (left, right) => {
+                                let error = RuntimeError::new(&error_code::INVALID_PLUS_OPERANDS, operator.span, "Interpreter.rs", "Must be all string or number for PLUS (+)")
+                                    .with_secondary(left_span, &format!("this has type {}", left.type_name()))
+                                    .with_secondary(right_span, &format!("this has type {}", right.type_name()));
+                                return Err(error);
+                            }
                         }
                     },
-                    TokenEnum::Greater => Literal::Bool(check_number(left)? > check_number(right)?),
-                    TokenEnum::GreaterEqual => Literal::Bool(check_number(left)? >= check_number(right)?),
-                    TokenEnum::Less => Literal::Bool(check_number(left)? < check_number(right)?),
-                    TokenEnum::LessEqual => Literal::Bool(check_number(left)? <= check_number(right)?),
-                    TokenEnum::BangEqual => Literal::Bool(!(left == right)),
-                    TokenEnum::EqualEqual => Literal::Bool(left == right),
-                    _ => return Exception::error(operator.line, "Interpreter.rs", "message")
+                    // NaN isn't ordered, every comparison with it is false.
+                    TokenEnum::Greater => Value::Bool(matches!(compare(left, left_span, right, right_span)?, Some(Ordering::Greater))),
+                    TokenEnum::GreaterEqual => Value::Bool(matches!(compare(left, left_span, right, right_span)?, Some(Ordering::Greater | Ordering::Equal))),
+                    TokenEnum::Less => Value::Bool(matches!(compare(left, left_span, right, right_span)?, Some(Ordering::Less))),
+                    TokenEnum::LessEqual => Value::Bool(matches!(compare(left, left_span, right, right_span)?, Some(Ordering::Less | Ordering::Equal))),
+                    TokenEnum::BangEqual => Value::Bool(left != right),
+                    TokenEnum::EqualEqual => Value::Bool(left == right),
+                    _ => return RuntimeError::error(&error_code::UNSUPPORTED_OPERATOR, operator.span, "Interpreter.rs", &format!("Unsupported binary operator '{}'.", operator.lexeme))
                 }
             },
             // __ => return Exception::error(1, "Interpreter.rs", "message")  rust complaining with that.
@@ -58,25 +458,197 @@ impl Interpreter {
     }
 }
 
-fn check_number(value: Literal) -> EvaluateResult<i64> {
+// The scope every script starts with.
+fn globals() -> Environment {
+    let mut globals = Environment::new();
+    let clock = NativeFunction { name: "clock", arity: 0, function: clock };
+    globals.define(clock.name, Value::Native(Rc::new(clock)));
+    globals
+}
+
+// Milliseconds since the Unix epoch, numbers are integers so seconds would be too coarse to time anything.
+fn clock(_: &[Value]) -> Value {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    Value::Number(elapsed.as_millis() as i64)
+}
+
+fn instance(value: &Value, span: Span, message: &str) -> EvaluateResult<Rc<RefCell<Instance>>> {
+    match value {
+        Value::Instance(instance) => Ok(instance.clone()),
+        other => Err(RuntimeError::new(&error_code::NOT_AN_INSTANCE, span, "Interpreter.rs", message)
+            .with_secondary(span, &format!("this has type {}", other.type_name()))),
+    }
+}
+
+// The operands of an arithmetic or comparison operator. Two integers stay integers,
+// bignums when either one is, and when either is a float both become floats.
+enum Numbers {
+    Integers(i64, i64),
+    Bigs(BigInt, BigInt),
+    Floats(f64, f64),
+}
+
+fn numbers(left: Value, left_span: Span, right: Value, right_span: Span) -> EvaluateResult<Numbers> {
+    match (check_number(left, left_span)?, check_number(right, right_span)?) {
+        (Value::Number(left), Value::Number(right)) => Ok(Numbers::Integers(left, right)),
+        (left @ Value::Float(_), right) | (left, right @ Value::Float(_)) => Ok(Numbers::Floats(float(&left), float(&right))),
+        (left, right) => Ok(Numbers::Bigs(big(left), big(right))),
+    }
+}
+
+// `+`, `-`, `*`, `/` and `%`. Integer division truncates and `%` takes the sign of the
+// left side, like in C. Dividing by zero is an error for floats too, instead of giving
+// infinity or NaN.
+fn arithmetic(mode: Arithmetic, operator: &Token, numbers: Numbers, right_span: Span) -> EvaluateResult<Value> {
+    let value = match (operator.token_type, numbers) {
+        (TokenEnum::Slash | TokenEnum::Percent, Numbers::Integers(_, 0) | Numbers::Floats(_, 0.0)) => return division_by_zero(operator, right_span),
+        (TokenEnum::Slash | TokenEnum::Percent, Numbers::Bigs(_, right)) if right.is_zero() => return division_by_zero(operator, right_span),
+        (_, Numbers::Integers(left, right)) => integers(mode, operator, left, right)?,
+        (_, Numbers::Bigs(left, right)) => bigs(mode, operator, left, right)?,
+        (TokenEnum::Plus, Numbers::Floats(left, right)) => Value::Float(left + right),
+        (TokenEnum::Minus, Numbers::Floats(left, right)) => Value::Float(left - right),
+        (TokenEnum::Star, Numbers::Floats(left, right)) => Value::Float(left * right),
+        (TokenEnum::Slash, Numbers::Floats(left, right)) => Value::Float(left / right),
+        (TokenEnum::Percent, Numbers::Floats(left, right)) => Value::Float(left % right),
+        _ => return unsupported(operator),
+    };
+
+    Ok(value)
+}
+
+// Integer arithmetic, the divisor isn't zero. Stays on i64 while the result fits and
+// only does the slow bignum work when it doesn't.
+fn integers(mode: Arithmetic, operator: &Token, left: i64, right: i64) -> EvaluateResult<Value> {
+    let result = match operator.token_type {
+        TokenEnum::Plus => left.checked_add(right),
+        TokenEnum::Minus => left.checked_sub(right),
+        TokenEnum::Star => left.checked_mul(right),
+        TokenEnum::Slash => left.checked_div(right),
+        TokenEnum::Percent => left.checked_rem(right),
+        _ => return unsupported(operator),
+    };
+
+    match result {
+        Some(value) => Ok(Value::Number(value)),
+        None => bigs(mode, operator, BigInt::from(left), BigInt::from(right)),
+    }
+}
+
+// Division truncates and `%` takes the sign of the left side, same as for i64.
+fn bigs(mode: Arithmetic, operator: &Token, left: BigInt, right: BigInt) -> EvaluateResult<Value> {
+    let exact = match operator.token_type {
+        TokenEnum::Plus => left + right,
+        TokenEnum::Minus => left - right,
+        TokenEnum::Star => left * right,
+        TokenEnum::Slash => left / right,
+        TokenEnum::Percent => left % right,
+        _ => return unsupported(operator),
+    };
+
+    fit(mode, operator, exact)
+}
+
+// Turns the exact result into a value the way the interpreter's mode says. Wrapping
+// keeps the lowest 64 bits, like i64::wrapping_* would give.
+fn fit(mode: Arithmetic, operator: &Token, exact: BigInt) -> EvaluateResult<Value> {
+    if let Some(value) = exact.to_i64() {
+        return Ok(Value::Number(value));
+    }
+
+    match mode {
+        Arithmetic::Bignum => Ok(Value::Big(exact)),
+        Arithmetic::Checked => RuntimeError::error(&error_code::INTEGER_OVERFLOW, operator.span, "Interpreter.rs", &format!("Integer overflow in '{}'.", operator.lexeme)),
+        Arithmetic::Wrapping => {
+            let bytes = exact.to_signed_bytes_le();
+            let mut low = [0; 8];
+            low.copy_from_slice(&bytes[..8]);
+            Ok(Value::Number(i64::from_le_bytes(low)))
+        }
+        Arithmetic::Saturating => Ok(Value::Number(if exact.sign() == Sign::Minus { i64::MIN } else { i64::MAX })),
+    }
+}
+
+fn unsupported<T>(operator: &Token) -> EvaluateResult<T> {
+    RuntimeError::error(&error_code::UNSUPPORTED_OPERATOR, operator.span, "Interpreter.rs", &format!("Unsupported arithmetic operator '{}'.", operator.lexeme))
+}
+
+// An integer or bignum against a float is compared exactly, not turned into a float like for
+// arithmetic, so it agrees with `==`.
+fn compare(left: Value, left_span: Span, right: Value, right_span: Span) -> EvaluateResult<Option<Ordering>> {
+    let ordering = match (check_number(left, left_span)?, check_number(right, right_span)?) {
+        (left @ (Value::Number(_) | Value::Big(_)), Value::Float(right)) => compare_integer_float(&big(left), right),
+        (Value::Float(left), right @ (Value::Number(_) | Value::Big(_))) => compare_integer_float(&big(right), left).map(Ordering::reverse),
+        (left, right) => match numbers(left, left_span, right, right_span)? {
+            Numbers::Integers(left, right) => left.partial_cmp(&right),
+            Numbers::Bigs(left, right) => left.partial_cmp(&right),
+            Numbers::Floats(left, right) => left.partial_cmp(&right),
+        },
+    };
+
+    Ok(ordering)
+}
+
+fn division_by_zero<T>(operator: &Token, right_span: Span) -> EvaluateResult<T> {
+    let message = match operator.token_type {
+        TokenEnum::Percent => "Modulo by zero.",
+        _ => "Division by zero.",
+    };
+    Err(RuntimeError::new(&error_code::DIVISION_BY_ZERO, operator.span, "Interpreter.rs", message)
+        .with_secondary(right_span, "this is zero"))
+}
+
+// Gives back the value when it's an integer or a float.
+fn check_number(value: Value, span: Span) -> EvaluateResult<Value> {
+    match value {
+        Value::Number(_) | Value::Big(_) | Value::Float(_) => Ok(value),
+        other => RuntimeError::error(&error_code::OPERAND_NOT_A_NUMBER, span, "Interpreter.rs/check_number", &format!("Operand must be a number, not {}.", other.type_name()))
+    }
+}
+
+// Bignums too large for a float become infinity.
+fn float(value: &Value) -> f64 {
     match value {
-        Literal::Number(value) => Ok(value),
-        __ => Exception::error(1, "Interpreter.rs/check_number", &format!("Operand {:?} Must be a number", __))
+        Value::Number(value) => *value as f64,
+        Value::Big(value) => value.to_f64().expect("every bignum converts"),
+        Value::Float(value) => *value,
+        _ => unreachable!("checked to be a number"),
     }
 }
 
-fn is_truthy(value: Literal) -> bool {
+fn big(value: Value) -> BigInt {
     match value {
-        Literal::Bool(false) | Literal::None => false,
-        _ => true
+        Value::Number(value) => BigInt::from(value),
+        Value::Big(value) => value,
+        _ => unreachable!("checked to be an integer"),
+    }
+}
+
+// Only a jump out of a statement matters to whoever ran it, the value of an
+// expression statement inside an if or a block isn't shown in the prompt.
+fn unwinding(flow: Flow) -> Flow {
+    match flow {
+        Flow::Normal(_) => Flow::Normal(None),
+        flow => flow,
+    }
+}
+
+// A break or continue without a label is for the innermost loop.
+fn targets(target: &Option<String>, label: Option<&str>) -> bool {
+    match target {
+        None => true,
+        Some(target) => Some(target.as_str()) == label,
     }
 }
 
+fn is_truthy(value: Value) -> bool {
+    !matches!(value, Value::Bool(false) | Value::None)
+}
+
 #[cfg(test)]
 mod tests {
     use core::assert_eq;
 
-    use crate::{parser::Parser, scanner::Scanner};
+    use crate::{exception::BaseException, parser::Parser, scanner::Scanner};
 
     use super::*;
 
@@ -86,9 +658,20 @@ mod tests {
         let tokens = scan.scan_tokens().unwrap();
         let mut binding = Parser::new(tokens);
         let parse = binding.parser();
-        let interpreter = Interpreter.evaluate( &parse.unwrap()).unwrap();
+        let interpreter = Interpreter::new().evaluate( &parse.unwrap()).unwrap();
 
-        assert_eq!(interpreter, Literal::Number(10));
+        assert_eq!(interpreter, Value::Number(10));
+    }
+
+    #[test]
+    fn negate_number() {
+        let mut scan = Scanner::new("-(2 + 3)");
+        let tokens = scan.scan_tokens().unwrap();
+        let mut binding = Parser::new(tokens);
+        let parse = binding.parser();
+        let interpreter = Interpreter::new().evaluate( &parse.unwrap()).unwrap();
+
+        assert_eq!(interpreter, Value::Number(-5));
     }
 
     #[test]
@@ -97,9 +680,9 @@ mod tests {
         let tokens = scan.scan_tokens().unwrap();
         let mut binding = Parser::new(tokens);
         let parse = binding.parser();
-        let interpreter = Interpreter.evaluate( &parse.unwrap()).unwrap();
+        let interpreter = Interpreter::new().evaluate( &parse.unwrap()).unwrap();
 
-        assert_eq!(interpreter, Literal::String("test".to_string()));
+        assert_eq!(interpreter, Value::String("test".to_string()));
     }
 
     #[test]
@@ -108,20 +691,535 @@ mod tests {
         let tokens = scan.scan_tokens().unwrap();
         let mut binding = Parser::new(tokens);
         let parse = binding.parser();
-        let interpreter = Interpreter.evaluate( &parse.unwrap());
+        let interpreter = Interpreter::new().evaluate( &parse.unwrap());
 
         assert!(interpreter.is_err());
         // Will try using assert_eq
     }
 
+    #[test]
+    fn error_points_at_the_operand() {
+        let mut scan = Scanner::new("1 +\n (2 * \"x\")");
+        let tokens = scan.scan_tokens().unwrap();
+        let mut binding = Parser::new(tokens);
+        let parse = binding.parser();
+        let error = Interpreter::new().evaluate( &parse.unwrap()).unwrap_err();
+
+        assert_eq!(error.span(), Span::new(10, 13, 2, 7));
+        assert_eq!(error.code(), &error_code::OPERAND_NOT_A_NUMBER);
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let mut scan = Scanner::new("0 / 0");
+        let tokens = scan.scan_tokens().unwrap();
+        let mut binding = Parser::new(tokens);
+        let parse = binding.parser();
+        let error = Interpreter::new().evaluate( &parse.unwrap()).unwrap_err();
+
+        assert_eq!(error.code(), &error_code::DIVISION_BY_ZERO);
+        assert_eq!(error.span(), Span::new(2, 3, 1, 3));
+    }
+
+    #[test]
+    fn modulo() {
+        let (result, output) = run("print 7 % 3;\nprint -7 % 3;\nprint 7.5 % 2;\n1 % 0;");
+
+        assert_eq!(output, "1\n-1\n1.5\n");
+        let error = result.unwrap_err();
+        assert_eq!(error.code(), &error_code::DIVISION_BY_ZERO);
+        assert_eq!(error.message(), "Modulo by zero.");
+        assert_eq!(error.span(), Span::new(44, 45, 4, 3));
+    }
+
+    #[test]
+    fn arithmetic_modes() {
+        let source = "print 9223372036854775807 + 1;\nprint -9223372036854775807 - 2;\nprint 4611686018427387904 * 4;\nprint -(-9223372036854775807 - 1);\nprint (-9223372036854775807 - 1) / -1;";
+        let outputs: Vec<String> = [Arithmetic::Wrapping, Arithmetic::Saturating]
+            .into_iter()
+            .map(|mode| {
+                let mut interpreter = Interpreter::with_output(Vec::new()).arithmetic(mode);
+                crate::run(source, &mut interpreter).unwrap();
+                String::from_utf8(interpreter.output().clone()).unwrap()
+            })
+            .collect();
+
+        assert_eq!(outputs[0], "-9223372036854775808\n9223372036854775807\n0\n-9223372036854775808\n-9223372036854775808\n");
+        assert_eq!(outputs[1], "9223372036854775807\n-9223372036854775808\n9223372036854775807\n9223372036854775807\n9223372036854775807\n");
+
+        let mut interpreter = Interpreter::with_output(Vec::new()).arithmetic(Arithmetic::Wrapping);
+        let error = crate::run("1 / 0;", &mut interpreter).unwrap_err();
+        assert_eq!(error.diagnostics()[0].code, Some(error_code::DIVISION_BY_ZERO.code));
+    }
+
+    #[test]
+    fn bignums() {
+        let source = "fun factorial(n) { if (n < 2) return 1; return n * factorial(n - 1); }\n\
+                      print factorial(25);\n\
+                      var big = 9223372036854775807 + 1;\n\
+                      print big;\n\
+                      print big - 1;\n\
+                      print big / 2 + big % 3;\n\
+                      print -(-9223372036854775807 - 1);\n\
+                      print 100000000000000000000 > 99999999999999999999;\n\
+                      print big == 9223372036854775808;\n\
+                      print big * 0.5;";
+        let mut interpreter = Interpreter::with_output(Vec::new());
+        crate::run(source, &mut interpreter).unwrap();
+
+        let output = String::from_utf8(interpreter.output().clone()).unwrap();
+        assert_eq!(output, "15511210043330985984000000\n9223372036854775808\n9223372036854775807\n4611686018427387906\n9223372036854775808\ntrue\ntrue\n4.611686018427388e18\n");
+
+        // Back to a plain integer once it fits.
+        let (result, _) = run("(9223372036854775807 + 10) - 20;");
+        assert_eq!(result.unwrap(), Some(Value::Number(9223372036854775797)));
+    }
+
+    #[test]
+    fn adding_bignums() {
+        let (_, output) = run("var big = 9223372036854775807 + 1;\nprint big + 1;\nprint 1 + 99999999999999999999999;\nprint 99999999999999999999999 + 0.5;\nprint big + -1;\nprint big == 9223372036854775808.0;\nprint big + 1 == 9223372036854775808.0;");
+
+        assert_eq!(output, "9223372036854775809\n100000000000000000000000\n1e23\n9223372036854775807\ntrue\nfalse\n");
+    }
+
+    #[test]
+    fn ordering_bignums_and_floats() {
+        let source = "var big = 9223372036854775807 + 2;\nvar f = 9223372036854775808.0;\n\
+                      print big > f; print big >= f; print big < f; print f < big; print f <= big;\n\
+                      print -big < -f; print big < 1e300; print big + 1 > 1.5;";
+        let (_, output) = run(source);
+
+        assert_eq!(output, "true\ntrue\nfalse\ntrue\ntrue\ntrue\ntrue\ntrue\n");
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        let mut scan = Scanner::new("9223372036854775807 + 1");
+        let tokens = scan.scan_tokens().unwrap();
+        let mut binding = Parser::new(tokens);
+        let parse = binding.parser();
+        let error = Interpreter::new().arithmetic(Arithmetic::Checked).evaluate( &parse.unwrap()).unwrap_err();
+
+        assert_eq!(error.code(), &error_code::INTEGER_OVERFLOW);
+    }
+
+    fn run(source: &str) -> (EvaluateResult<Option<Value>>, String) {
+        let program = crate::compile(source).unwrap();
+
+        let mut interpreter = Interpreter::with_output(Vec::new());
+        let result = interpreter.interpret(&program);
+        (result, String::from_utf8(interpreter.output().clone()).unwrap())
+    }
+
+    #[test]
+    fn print_statements() {
+        let (result, output) = run("print 1 + 2;\nprint \"a\" + \"b\";\n10 / 2;");
+
+        assert_eq!(output, "3\nab\n");
+        assert_eq!(result.unwrap(), Some(Value::Number(5)));
+    }
+
+    #[test]
+    fn stops_at_the_first_error() {
+        let (result, output) = run("print 1;\nprint -\"a\";\nprint 2;");
+
+        assert_eq!(output, "1\n");
+        assert_eq!(result.unwrap_err().line(), 2);
+    }
+
+    #[test]
+    fn global_variables() {
+        let (result, output) = run("var a = 1;\nvar b;\nprint b;\nb = a = a + 1;\nprint a + b;\nvar a = \"again\";\na;");
+
+        assert_eq!(output, "null\n4\n");
+        assert_eq!(result.unwrap(), Some(Value::String("again".to_string())));
+    }
+
+    #[test]
+    fn undefined_variable() {
+        let (result, _) = run("var a = 1;\nprint a +\n  b;");
+        let error = result.unwrap_err();
+
+        assert_eq!(error.code(), &error_code::UNDEFINED_VARIABLE);
+        assert_eq!(error.span(), Span::new(23, 24, 3, 3));
+
+        let (result, _) = run("c = 1;");
+        assert_eq!(result.unwrap_err().message(), "Undefined variable 'c'.");
+    }
+
+    #[test]
+    fn block_scopes() {
+        let (result, output) = run("\
+var a = \"global a\";
+var b = \"global b\";
+{
+  var a = \"outer a\";
+  {
+    var a = \"inner a\";
+    print a;
+    b = \"assigned from inside\";
+  }
+  print a;
+}
+print a;
+print b;");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "inner a\nouter a\nglobal a\nassigned from inside\n");
+    }
+
+    #[test]
+    fn inner_declarations_do_not_leak() {
+        let (result, output) = run("{ var hidden = 1; print hidden; }\nprint hidden;");
+
+        assert_eq!(output, "1\n");
+        assert_eq!(result.unwrap_err().message(), "Undefined variable 'hidden'.");
+    }
+
+    #[test]
+    fn scope_is_restored_after_an_error() {
+        let mut interpreter = Interpreter::with_output(Vec::new());
+
+        assert!(crate::run("var a = 1; { var a = 2; -\"x\"; }", &mut interpreter).is_err());
+        assert_eq!(crate::run("a;", &mut interpreter).unwrap(), Some(Value::Number(1)));
+    }
+
+    #[test]
+    fn if_else() {
+        let (_, output) = run("\
+if (1 > 2) print \"then\"; else print \"else\";
+if (null) print \"no\";
+if (0) { print \"zero is truthy\"; }");
+
+        assert_eq!(output, "else\nzero is truthy\n");
+    }
+
+    #[test]
+    fn logical_operators_return_the_deciding_operand() {
+        let (_, output) = run("\
+print \"hi\" or 2;
+print null or \"yes\";
+print false and 1;
+print 1 and \"both\";");
+
+        assert_eq!(output, "hi\nyes\nfalse\nboth\n");
+    }
+
+    #[test]
+    fn short_circuit() {
+        let (result, output) = run("\
+var calls = 0;
+true or (calls = calls + 1);
+false and (calls = calls + 1);
+null or (calls = calls + 1);
+print calls;
+false and undefined;");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "1\n");
+    }
+
+    #[test]
+    fn loops() {
+        let (_, output) = run("\
+var i = 0;
+while (i < 3) { print i; i = i + 1; }
+for (var a = 0; a < 10; a = a + 4) print a;
+var fib = 0;
+for (var next = 1; fib < 10; next = fib + next) { print fib; var old = fib; fib = next; next = old; }");
+
+        assert_eq!(output, "0\n1\n2\n0\n4\n8\n0\n1\n1\n2\n3\n5\n8\n");
+    }
+
+    #[test]
+    fn for_variable_is_scoped_to_the_loop() {
+        let (result, _) = run("for (var i = 0; i < 1; i = i + 1) {}\nprint i;");
+
+        assert_eq!(result.unwrap_err().message(), "Undefined variable 'i'.");
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let (_, output) = run("\
+for (var i = 0; i < 10; i = i + 1) {
+  if (i == 1) continue;
+  if (i == 4) break;
+  print i;
+}
+var n = 0;
+while (true) { n = n + 1; if (n < 3) continue; break; }
+print n;");
+
+        assert_eq!(output, "0\n2\n3\n3\n");
+    }
+
+    #[test]
+    fn labelled_loops() {
+        let (_, output) = run("\
+outer: for (var i = 0; i < 3; i = i + 1) {
+  for (var j = 0; j < 3; j = j + 1) {
+    if (j == 1) continue outer;
+    if (i == 2) break outer;
+    print i * 10 + j;
+  }
+  print \"never\";
+}
+print \"done\";");
+
+        assert_eq!(output, "0\n10\ndone\n");
+    }
+
+    #[test]
+    fn break_leaves_the_block_scope() {
+        let (result, output) = run("var a = 1; while (true) { var a = 2; break; } print a;");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "1\n");
+    }
+
+    #[test]
+    fn floats() {
+        let (result, output) = run("print 1.5 + 2;\nprint 7 / 2;\nprint 7 / 2.0;\nprint 2 * 3.0;\nprint -0.5;\nprint 1 == 1.0;\nprint 1 < 1.5;\nprint 1e20 * 1e20;\n0.1 + 0.2;");
+
+        assert_eq!(output, "3.5\n3\n3.5\n6.0\n-0.5\ntrue\ntrue\n1e40\n");
+        assert_eq!(result.unwrap(), Some(Value::Float(0.30000000000000004)));
+    }
+
+    #[test]
+    fn integer_and_float_equality() {
+        let (_, output) = run("print 9007199254740993 == 9007199254740992.0;\nprint 9007199254740992 == 9007199254740992.0;\nprint 9223372036854775807 == 9223372036854775808.0;\nprint 1 != 1.5;");
+
+        assert_eq!(output, "false\ntrue\nfalse\ntrue\n");
+    }
+
+    #[test]
+    fn bignum_division_by_zero() {
+        for source in ["99999999999999999999 / 0;", "99999999999999999999 % 0;"] {
+            let (result, _) = run(source);
+
+            assert_eq!(result.unwrap_err().code(), &error_code::DIVISION_BY_ZERO);
+        }
+    }
+
+    #[test]
+    fn integer_and_float_ordering() {
+        let source = "var i = 9007199254740993;\nvar f = 9007199254740992.0;\n\
+                      print i > f; print i >= f; print i < f; print i <= f;\n\
+                      print f < i; print f >= i;\n\
+                      print -3 < -2.5; print 2 > 1.5; print 1 <= 1.0; print 0 < 0.0 / 1;\n\
+                      print 9223372036854775807 < 9223372036854775808.0;";
+        let (_, output) = run(source);
+
+        assert_eq!(output, "true\ntrue\nfalse\nfalse\ntrue\nfalse\ntrue\ntrue\ntrue\nfalse\ntrue\n");
+    }
+
+    #[test]
+    fn float_division_by_zero() {
+        let (result, _) = run("1.5 / -0.0;");
+
+        assert_eq!(result.unwrap_err().code(), &error_code::DIVISION_BY_ZERO);
+    }
+
+    #[test]
+    fn comma() {
+        let (result, output) = run("var a = 1;\nfun f(x, y) { return x + y; }\nprint (a = a + 1, a * 10);\nprint f((a, 5), a);\na, \"last\";");
+
+        assert_eq!(output, "20\n7\n");
+        assert_eq!(result.unwrap(), Some(Value::String("last".to_string())));
+    }
+
+    #[test]
+    fn conditional() {
+        let (result, output) = run("var a = 0;\nprint true ? \"yes\" : (a = 1);\nprint null ? 1 : false ? 2 : 3;\nprint a;\n1 > 2 ? 1 / 0 : 0;");
+
+        assert_eq!(output, "yes\n3\n0\n");
+        assert_eq!(result.unwrap(), Some(Value::Number(0)));
+    }
+
+    #[test]
+    fn functions() {
+        let source = "fun fib(n) { if (n < 2) return n; return fib(n - 2) + fib(n - 1); }\n\
+                      fun greet(name) { print \"hi \" + name; }\n\
+                      print fib(15);\n\
+                      print greet(\"totx\");\n\
+                      print fib;";
+        let (result, output) = run(source);
+
+        assert!(result.is_ok());
+        assert_eq!(output, "610\nhi totx\nnull\n<fn fib>\n");
+    }
+
+    #[test]
+    fn return_leaves_loops() {
+        let (result, output) = run("fun first() { for (var i = 1;; i = i + 1) { while (true) return i; } }\nprint first();");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "1\n");
+    }
+
+    #[test]
+    fn make_counter() {
+        let source = "fun makeCounter() { var i = 0; fun count() { i = i + 1; return i; } return count; }\n\
+                      var first = makeCounter();\n\
+                      var second = makeCounter();\n\
+                      print first(); print first(); print second(); print first();";
+        let (result, output) = run(source);
+
+        assert!(result.is_ok());
+        assert_eq!(output, "1\n2\n1\n3\n");
+    }
+
+    #[test]
+    fn closures_share_what_they_capture() {
+        let source = "var add; var total;\n\
+                      { var sum = 0; fun adder(n) { sum = sum + n; } fun getter() { return sum; } add = adder; total = getter; }\n\
+                      add(10); add(5);\n\
+                      print total();";
+        let (result, output) = run(source);
+
+        assert!(result.is_ok());
+        assert_eq!(output, "15\n");
+    }
+
+    #[test]
+    fn closures_outlive_loops() {
+        let source = "fun compose(f, g) { fun both() { f(); g(); } return both; }\n\
+                      fun nothing() {}\n\
+                      var all = nothing;\n\
+                      for (var i = 1; i <= 3; i = i + 1) { var j = i; fun show() { print j; } all = compose(all, show); }\n\
+                      all();";
+        let (result, output) = run(source);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(output, "1\n2\n3\n");
+    }
+
+    #[test]
+    fn closures_ignore_later_shadowing() {
+        let source = "var a = \"global\";\n\
+                      { fun show() { print a; } show(); var a = \"block\"; show(); print a; }";
+        let (result, output) = run(source);
+
+        assert!(result.is_ok());
+        assert_eq!(output, "global\nglobal\nblock\n");
+    }
+
+    #[test]
+    fn classes() {
+        let source = "class Counter {\n\
+                        init(start) { this.count = start; }\n\
+                        add(n) { this.count = this.count + n; return this; }\n\
+                      }\n\
+                      var counter = Counter(10);\n\
+                      counter.add(1).add(2);\n\
+                      var add = counter.add;\n\
+                      add(3);\n\
+                      print counter.count;\n\
+                      print Counter;\n\
+                      print counter;\n\
+                      print counter.init(0) == counter;\n\
+                      print counter.count;";
+        let (result, output) = run(source);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(output, "16\nCounter\nCounter instance\ntrue\n0\n");
+    }
+
+    #[test]
+    fn fields_shadow_methods() {
+        let (result, output) = run("class A { f() { return 1; } }\nvar a = A();\nfun two() { return 2; }\na.f = two;\nprint a.f();");
+
+        assert!(result.is_ok());
+        assert_eq!(output, "2\n");
+    }
+
+    #[test]
+    fn inheritance() {
+        let source = "class A { init(name) { this.name = name; } hello() { return \"A \" + this.name; } }\n\
+                      class B < A { hello() { return \"B, \" + super.hello(); } }\n\
+                      class C < B { init() { super.init(\"c\"); } }\n\
+                      var c = C();\n\
+                      print c.hello();\n\
+                      var hello = c.hello;\n\
+                      print hello();";
+        let (result, output) = run(source);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(output, "B, A c\nB, A c\n");
+    }
+
+    #[test]
+    fn inheritance_errors() {
+        let (result, _) = run("var A = 1;\nclass B < A {}");
+        let error = result.unwrap_err();
+        assert_eq!(error.code(), &error_code::SUPERCLASS_NOT_A_CLASS);
+        assert_eq!(error.span(), Span::new(21, 22, 2, 11));
+
+        let (result, _) = run("class A {}\nclass B < A { f() { return super.f(); } }\nB().f();");
+        assert_eq!(result.unwrap_err().message(), "Undefined property 'f'.");
+    }
+
+    #[test]
+    fn property_errors() {
+        let (result, _) = run("class Point {}\nvar point = Point();\nprint point.x;");
+        let error = result.unwrap_err();
+        assert_eq!(error.code(), &error_code::UNDEFINED_PROPERTY);
+        assert_eq!(error.message(), "Undefined property 'x'.");
+        assert_eq!(error.span(), Span::new(48, 49, 3, 13));
+
+        let (result, _) = run("var a = 1;\na.b = 2;");
+        assert_eq!(result.unwrap_err().code(), &error_code::NOT_AN_INSTANCE);
+
+        let (result, _) = run("class Point { init(x, y) {} }\nPoint(1);");
+        assert_eq!(result.unwrap_err().code(), &error_code::WRONG_ARITY);
+    }
+
+    #[test]
+    fn functions_do_not_see_the_caller_scope() {
+        let (result, _) = run("fun f() { return a; }\n{ var a = 1; f(); }");
+
+        assert_eq!(result.unwrap_err().code(), &error_code::UNDEFINED_VARIABLE);
+    }
+
+    #[test]
+    fn call_errors() {
+        let (result, _) = run("fun add(a, b) { return a + b; }\nadd(1\n);");
+        let error = result.unwrap_err();
+        assert_eq!(error.code(), &error_code::WRONG_ARITY);
+        assert_eq!(error.message(), "Expected 2 arguments but got 1.");
+        assert_eq!(error.span(), Span::new(38, 39, 3, 1));
+
+        let (result, _) = run("\"add\"(1);");
+        let error = result.unwrap_err();
+        assert_eq!(error.code(), &error_code::NOT_CALLABLE);
+        assert_eq!(error.span(), Span::new(7, 8, 1, 8));
+    }
+
+    #[test]
+    fn stack_overflow() {
+        // Test threads get a small stack, the binary runs on one of STACK_SIZE.
+        let thread = std::thread::Builder::new().stack_size(STACK_SIZE);
+        let handle = thread.spawn(|| run("fun forever(n) { return forever(n + 1); }\nforever(0);").0.unwrap_err().code().code).unwrap();
+
+        assert_eq!(handle.join().unwrap(), error_code::STACK_OVERFLOW.code);
+    }
+
+    #[test]
+    fn native_clock() {
+        let (result, output) = run("print clock() > 0;\nclock;");
+
+        assert_eq!(output, "true\n");
+        assert_eq!(result.unwrap().unwrap().to_string(), "<native fn clock>");
+    }
+
     #[test]
     fn expressions() {
         let mut scan = Scanner::new("(10 + 2) / 2");
         let tokens = scan.scan_tokens().unwrap();
         let mut binding = Parser::new(tokens);
         let parse = binding.parser();
-        let interpreter = Interpreter.evaluate( &parse.unwrap()).unwrap();
+        let interpreter = Interpreter::new().evaluate( &parse.unwrap()).unwrap();
 
-        assert_eq!(interpreter, Literal::Number(6));
+        assert_eq!(interpreter, Value::Number(6));
     }
 }
@@ -0,0 +1,70 @@
+pub mod ast;
+pub mod diagnostic;
+pub mod environment;
+pub mod error_code;
+pub mod exception;
+pub mod interpreter;
+pub mod parser;
+pub mod resolver;
+pub mod scanner;
+pub mod span;
+pub mod token;
+pub mod value;
+
+use std::io::Write;
+
+use ast::Stmt;
+use diagnostic::Diagnostic;
+use exception::Error;
+use interpreter::Interpreter;
+use parser::Parser;
+use resolver::Resolver;
+use scanner::Scanner;
+use value::Value;
+
+// A script that passed every check and is ready to run. Only `compile` makes one, so
+// the interpreter never sees a tree the resolver skipped.
+pub struct Program {
+    statements: Vec<Stmt>,
+    // Things that look wrong but don't stop the script, like unused variables.
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl Program {
+    pub fn statements(&self) -> &[Stmt] {
+        &self.statements
+    }
+}
+
+// Scans, parses and resolves `source` without running it.
+pub fn compile(source: &str) -> Result<Program, Error> {
+    compile_with(source, false)
+}
+
+// Same as `compile` for an entry typed at the prompt, where the last statement can
+// leave out its ';'.
+pub fn compile_entry(source: &str) -> Result<Program, Error> {
+    compile_with(source, true)
+}
+
+fn compile_with(source: &str, prompt: bool) -> Result<Program, Error> {
+    let tokens = Scanner::new(source).scan_tokens()?;
+    let (mut statements, errors) = Parser::new(tokens).prompt(prompt).parse();
+    if !errors.is_empty() {
+        return Err(errors.into());
+    }
+
+    let (errors, warnings) = Resolver::new().resolve(&mut statements);
+    if !errors.is_empty() {
+        return Err(errors.into());
+    }
+
+    Ok(Program { statements, warnings })
+}
+
+// Compiles and runs `source`, what embedding the language takes. Warnings are dropped,
+// `compile` gives them. Returns the value of the last statement when it's an expression.
+pub fn run<W: Write>(source: &str, interpreter: &mut Interpreter<W>) -> Result<Option<Value>, Error> {
+    let program = compile(source)?;
+    Ok(interpreter.interpret(&program)?)
+}
@@ -1,37 +1,176 @@
-mod ast;
-mod scanner;
-mod token;
-mod exception;
-mod parser;
-mod interpreter;
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    process, thread,
+};
 
-use clap::Parser;
-use interpreter::Interpreter;
-use std::{ env, fs, path::PathBuf, process };
+mod repl;
 
-use scanner::Scanner;
+use clap::{Parser, ValueEnum};
+use repl::{History, Repl, Status};
+use totx::{
+    diagnostic::{Diagnostic, Renderer},
+    error_code,
+    interpreter::{self, Interpreter},
+};
 
+// Exit codes borrowed from the BSD sysexits.h, the same ones the book uses.
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_NOINPUT: i32 = 66;
+const EX_SOFTWARE: i32 = 70;
 
 #[derive(Parser, Debug)]
+#[command(name = "totx", version, about = "Tree-walking interpreter for the totx language")]
 struct Args {
-    file: PathBuf,
+    /// Script to run. Use `-` to read it from stdin, or leave it empty to start the prompt.
+    file: Option<PathBuf>,
+
+    /// Print the long explanation of an error code, like T0001, and exit.
+    #[arg(long, value_name = "CODE", conflicts_with = "file")]
+    explain: Option<String>,
+
+    /// How errors are written to stderr.
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum ErrorFormat {
+    /// The source line with the error underlined.
+    Human,
+    /// One JSON object per error, one per line.
+    Json,
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    // let args = Args::parse();
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(err) => {
+            let code = if err.use_stderr() { EX_USAGE } else { 0 };
+            let _ = err.print();
+            process::exit(code);
+        }
+    };
+
+    if let Some(code) = args.explain {
+        process::exit(explain(&code));
+    }
+
+    // Deep recursion in a script needs more stack than the main thread has.
+    let interpreter = thread::Builder::new().stack_size(interpreter::STACK_SIZE);
+    let code = interpreter
+        .spawn(move || match args.file {
+            Some(path) => run_file(&path, args.error_format),
+            None => run_prompt(),
+        })
+        .and_then(|handle| handle.join().map_err(|_| io::Error::other("the interpreter panicked")))
+        .unwrap_or_else(|error| {
+            eprintln!("totx: {}", error);
+            EX_SOFTWARE
+        });
+
+    process::exit(code);
+}
+
+fn explain(code: &str) -> i32 {
+    match error_code::lookup(code) {
+        Some(error) => {
+            println!("{}: {}\n\n{}", error.code, error.title, error.explanation);
+            0
+        }
+        None => {
+            eprintln!("totx: '{}' is not a known error code", code);
+            EX_USAGE
+        }
+    }
+}
+
+fn run_file(path: &PathBuf, format: ErrorFormat) -> i32 {
+    let source = if path.as_os_str() == "-" {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(path)
+    };
+
+    let name = if path.as_os_str() == "-" {
+        "<stdin>".to_string()
+    } else {
+        path.display().to_string()
+    };
 
-    if args.len() > 2 {
-        dbg!("Usage: totx [script]");
-        process::exit(64);
+    match source {
+        Ok(source) => run(&source, &name, format),
+        Err(err) => {
+            eprintln!("totx: can't read '{}': {}", path.display(), err);
+            EX_NOINPUT
+        }
     }
+}
+
+fn run_prompt() -> i32 {
+    let stdin = io::stdin();
+    let mut repl = Repl::new(io::stdout(), io::stderr(), History::load(History::default_path())).color(use_color());
+
+    loop {
+        print!("{}", repl.prompt());
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        match stdin.read_line(&mut line) {
+            Ok(0) => return 0,
+            Ok(_) => {
+                if repl.feed(&line) == Status::Quit {
+                    return 0;
+                }
+            }
+            Err(err) => {
+                eprintln!("totx: {}", err);
+                return EX_NOINPUT;
+            }
+        }
+    }
+}
+
+// Returns the exit code for the script: 0 when everything went fine. Warnings are
+// shown but don't change it.
+fn run(source: &str, name: &str, format: ErrorFormat) -> i32 {
+    let result = totx::compile(source).and_then(|program| {
+        if !program.warnings.is_empty() {
+            report(source, name, format, &program.warnings);
+        }
+        Ok(Interpreter::new().interpret(&program)?)
+    });
 
-    let source = fs::read_to_string(&"input.tx").expect("Unable to read the file");
+    match result {
+        Ok(_) => 0,
+        Err(err) => {
+            report(source, name, format, &err.diagnostics());
+            if err.is_compile_time() {
+                EX_DATAERR
+            } else {
+                EX_SOFTWARE
+            }
+        }
+    }
+}
+
+fn report(source: &str, name: &str, format: ErrorFormat, diagnostics: &[Diagnostic]) {
+    let renderer = Renderer::new(source, name).color(use_color());
+    for diagnostic in diagnostics {
+        match format {
+            ErrorFormat::Human => eprint!("{}", renderer.render(diagnostic)),
+            ErrorFormat::Json => eprintln!("{}", renderer.json(diagnostic)),
+        }
+    }
+
+    if let (ErrorFormat::Human, Some(code)) = (format, diagnostics.iter().find_map(|diagnostic| diagnostic.code)) {
+        eprintln!("For more information about an error, try `totx --explain {}`.", code);
+    }
+}
 
-    let mut scanner = Scanner::new(&source);
-    let tokens = scanner.scan_tokens().unwrap();
-    let mut parser = parser::Parser::new(tokens);
-    let parse = parser.parser();
-    let interpreter = Interpreter.interpret(&parse.unwrap()).unwrap();
-    println!("{:?}", interpreter);
+// Colours only make sense on a terminal, and https://no-color.org asks us to respect NO_COLOR.
+fn use_color() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()
 }
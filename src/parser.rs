@@ -9,16 +9,58 @@
 
 // Name	           Operators	Associates
 // Comma Op.	      ,    	    Left
+// Assignment	      =    	    Right
+// Conditional	     ?:    	    Right
+// Logic OR	         or    	    Left
+// Logic AND	        and    	    Left
 // Equality	        == !=	    Left
 // Comparison	  > >= < <=	    Left
 // Term	             - +	    Left
-// Factor	         / *	    Left
+// Factor	       / * %	    Left
 // Unary	         ! -	    Right
+// Call	        a() a.b	    Left
+
+// Statements
+
+// program        → declaration* EOF
+// declaration    → classDecl | funDecl | varDecl | statement
+// classDecl      → "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}"
+// funDecl        → "fun" function
+// function       → IDENTIFIER "(" parameters? ")" block
+// parameters     → IDENTIFIER ( "," IDENTIFIER )*
+// varDecl        → "var" IDENTIFIER ( "=" expression )? ";"
+// statement      → exprStmt | forStmt | ifStmt | printStmt | whileStmt | block
+//                | breakStmt | continueStmt | returnStmt | labelled
+// returnStmt     → "return" expression? ";"
+// labelled       → IDENTIFIER ":" ( forStmt | whileStmt )
+// breakStmt      → "break" IDENTIFIER? ";"
+// continueStmt   → "continue" IDENTIFIER? ";"
+// forStmt        → "for" "(" ( varDecl | exprStmt | ";" ) expression? ";" expression? ")" statement
+// ifStmt         → "if" "(" expression ")" statement ( "else" statement )?
+// whileStmt      → "while" "(" expression ")" statement
+// block          → "{" declaration* "}"
+// exprStmt       → expression ";"
+// printStmt      → "print" expression ";"
+//
+// The ';' can be left out on the last statement, handy in the prompt.
+
+use std::rc::Rc;
 
 use crate::{
-    ast::Expression, exception, token::{Token, TokenEnum}
+    ast::{Expression, FunctionDeclaration, Stmt},
+    error_code::{self, ErrorCode},
+    exception::{BaseException, ParseError},
+    span::Span,
+    token::{Token, TokenEnum},
 };
 
+type Result<T> = std::result::Result<T, ParseError>;
+
+// Keeps the arguments and parameters in the range a call can handle comfortably.
+const MAX_ARGUMENTS: usize = 255;
+// Deeper input is an error instead of overflowing the stack, here or in the passes after.
+const MAX_NESTING: usize = 255;
+
 // #[derive(Debug, Clone)]
 // pub struct ParserError {
 //     token: Token,
@@ -30,146 +72,703 @@ use crate::{
 #[derive(Debug)]
 pub struct Parser {
     tokens: Vec<Token>,
-    current: usize
+    current: usize,
+    // Syntax errors found so far, the parser recovers from them and keeps going.
+    errors: Vec<ParseError>,
+    // Labels of the loops we're inside, innermost last. Unlabelled loops are None.
+    loops: Vec<Option<String>>,
+    // How many nested expressions and statements we're inside.
+    depth: usize,
+    // Set once the input went past MAX_NESTING. The rest of it is skipped, every level
+    // we unwind from would only add a missing ')' or '}' error.
+    too_deep: bool,
+    // At the prompt the last statement doesn't need its ';'.
+    prompt: bool,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
         Self {
             tokens,
-            current: 0
+            current: 0,
+            errors: Vec::new(),
+            loops: Vec::new(),
+            depth: 0,
+            too_deep: false,
+            prompt: false,
         }
     }
 
-    pub fn parser(&mut self) -> exception::Result<Expression> {
+    pub fn prompt(mut self, prompt: bool) -> Self {
+        self.prompt = prompt;
+        self
+    }
+
+    pub fn parser(&mut self) -> Result<Expression> {
         self.expression()
     }
 
-    fn expression(&mut self) -> exception::Result<Expression> {
-        self.comma()
+    // Parses the whole program. A syntax error doesn't stop it: the error is recorded,
+    // the parser skips to the next statement and carries on, so we get every statement
+    // that did parse plus all the errors in one go.
+    pub fn parse(&mut self) -> (Vec<Stmt>, Vec<ParseError>) {
+        let mut statements = Vec::new();
+
+        while !self.is_at_end() {
+            statements.extend(self.recovering_declaration());
+        }
+
+        (statements, std::mem::take(&mut self.errors))
+    }
+
+    // A declaration, or None when it had a syntax error. The error is kept and
+    // the tokens up to the next statement are skipped.
+    fn recovering_declaration(&mut self) -> Option<Stmt> {
+        match self.nested(Self::declaration) {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                if !self.too_deep || error.code() == &error_code::NESTING_TOO_DEEP {
+                    self.errors.push(error);
+                }
+                self.synchronize();
+                None
+            }
+        }
     }
 
-    fn comma(&mut self) -> exception::Result<Expression> {
-        let mut expr = self.equality()?;
+    fn declaration(&mut self) -> Result<Stmt> {
+        if self.matching(vec![TokenEnum::Class]) {
+            return self.class_declaration();
+        }
+
+        if self.matching(vec![TokenEnum::Fun]) {
+            let keyword = self.previous().span;
+            let mut declaration = self.function("function")?;
+            declaration.span = keyword.to(declaration.span);
+            return Ok(Stmt::Function { declaration: Rc::new(declaration) });
+        }
+
+        if self.matching(vec![TokenEnum::Var]) {
+            return self.var_declaration();
+        }
+
+        self.statement()
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt> {
+        let keyword = self.previous().span;
+        let name = self.consume(TokenEnum::Identifier, "Expect class name.")?;
+
+        let superclass = if self.matching(vec![TokenEnum::Less]) {
+            let name = self.consume(TokenEnum::Identifier, "Expect superclass name.")?;
+            Some(Expression::Variable { span: name.span, name, depth: None })
+        } else {
+            None
+        };
+
+        let brace = self.consume(TokenEnum::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenEnum::RightBrace) && !self.is_at_end() {
+            methods.push(Rc::new(self.function("method")?));
+        }
+
+        let end = self
+            .consume(TokenEnum::RightBrace, "Expect '}' after class body.")
+            .map_err(|error| error.with_secondary(brace.span, "to match this '{'"))?;
+
+        Ok(Stmt::Class { name, superclass, methods, span: keyword.to(end.span) })
+    }
+
+    // Everything after `fun`, methods don't have the keyword. `kind` says what's being
+    // declared in the error messages. The span starts at the name.
+    fn function(&mut self, kind: &str) -> Result<FunctionDeclaration> {
+        let name = self.consume(TokenEnum::Identifier, &format!("Expect {} name.", kind))?;
+        let paren = self.consume(TokenEnum::LeftParen, &format!("Expect '(' after {} name.", kind))?;
+
+        let mut params: Vec<Token> = Vec::new();
+        if !self.check(TokenEnum::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let message = format!("Can't have more than {} parameters.", MAX_ARGUMENTS);
+                    self.errors.push(Self::error_at(&error_code::TOO_MANY_ARGUMENTS, &self.peek(), &message));
+                }
+
+                params.push(self.consume(TokenEnum::Identifier, "Expect parameter name.")?);
+
+                if !self.matching(vec![TokenEnum::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenEnum::RightParen, "Expect ')' after parameters.")
+            .map_err(|error| error.with_secondary(paren.span, "to match this '('"))?;
+        self.consume(TokenEnum::LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
+
+        // Loops around the declaration don't reach into the body, `break` there is an error.
+        let loops = std::mem::take(&mut self.loops);
+        let body = self.block();
+        self.loops = loops;
+
+        let Stmt::Block { statements, span } = body? else {
+            unreachable!("block always gives a Stmt::Block");
+        };
+
+        Ok(FunctionDeclaration { span: name.span.to(span), name, params, body: statements })
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt> {
+        let keyword = self.previous().span;
+        let name = self.consume(TokenEnum::Identifier, "Expect variable name.")?;
+
+        let initializer = if self.matching(vec![TokenEnum::Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        let end = self.end_of_statement("Expect ';' after variable declaration.")?;
+        Ok(Stmt::Var { name, initializer, span: keyword.to(end) })
+    }
+
+    fn statement(&mut self) -> Result<Stmt> {
+        if self.check(TokenEnum::Identifier) && self.check_next(TokenEnum::Colon) {
+            return self.labelled_statement();
+        }
+
+        if self.matching(vec![TokenEnum::Break, TokenEnum::Continue]) {
+            return self.jump_statement();
+        }
+
+        if self.matching(vec![TokenEnum::For]) {
+            return self.for_statement(None);
+        }
+
+        if self.matching(vec![TokenEnum::If]) {
+            return self.if_statement();
+        }
+
+        if self.matching(vec![TokenEnum::Print]) {
+            return self.print_statement();
+        }
+
+        if self.matching(vec![TokenEnum::Return]) {
+            return self.return_statement();
+        }
+
+        if self.matching(vec![TokenEnum::While]) {
+            return self.while_statement(None);
+        }
+
+        if self.matching(vec![TokenEnum::LeftBrace]) {
+            return self.block();
+        }
+
+        self.expression_statement()
+    }
+
+    fn labelled_statement(&mut self) -> Result<Stmt> {
+        let label = self.advance();
+        self.advance();
+
+        if self.matching(vec![TokenEnum::For]) {
+            return self.for_statement(Some(label));
+        }
+
+        if self.matching(vec![TokenEnum::While]) {
+            return self.while_statement(Some(label));
+        }
+
+        Self::error(&error_code::EXPECTED_TOKEN, self.peek(), "Expect a loop after a label.")
+            .map_err(|error| error.with_secondary(label.span, "this label"))
+    }
+
+    // `break` and `continue`. Using them outside a loop is recorded as an error, but
+    // the statement itself parsed fine so there's nothing to recover from.
+    fn jump_statement(&mut self) -> Result<Stmt> {
+        let keyword = self.previous();
+        let label = if self.check(TokenEnum::Identifier) {
+            Some(self.advance())
+        } else {
+            None
+        };
+        let end = self.end_of_statement(&format!("Expect ';' after '{}'.", keyword.lexeme))?;
+
+        if self.loops.is_empty() {
+            let message = format!("Can't use '{}' outside of a loop.", keyword.lexeme);
+            self.errors.push(Self::error_at(&error_code::JUMP_OUTSIDE_LOOP, &keyword, &message));
+        } else if let Some(label) = &label {
+            if !self.loops.contains(&Some(label.lexeme.clone())) {
+                let message = format!("There's no loop labelled '{}' around this '{}'.", label.lexeme, keyword.lexeme);
+                self.errors.push(Self::error_at(&error_code::UNDEFINED_LABEL, label, &message));
+            }
+        }
+
+        let span = keyword.span.to(end);
+        Ok(match keyword.token_type {
+            TokenEnum::Break => Stmt::Break { label, span },
+            _ => Stmt::Continue { label, span },
+        })
+    }
+
+    // Parses a loop body keeping track of the loop, so `break` and `continue` know they're fine.
+    fn loop_body(&mut self, label: &Option<Token>) -> Result<Stmt> {
+        self.loops.push(label.as_ref().map(|label| label.lexeme.clone()));
+        let body = self.nested(Self::statement);
+        self.loops.pop();
+
+        body
+    }
+
+    // There's no for in the tree, it becomes a while:
+    // for (init; condition; increment) body  →  { init; while (condition) body, increment }
+    // The increment stays apart from the body so `continue` doesn't skip it.
+    fn for_statement(&mut self, label: Option<Token>) -> Result<Stmt> {
+        let keyword = label.as_ref().map_or(self.previous().span, |label| label.span);
+        self.consume(TokenEnum::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.matching(vec![TokenEnum::SemiColon]) {
+            None
+        } else if self.matching(vec![TokenEnum::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(TokenEnum::SemiColon) {
+            Expression::Literal { value: crate::token::Literal::Bool(true), span: self.peek().span }
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenEnum::SemiColon, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(TokenEnum::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenEnum::RightParen, "Expect ')' after for clauses.")?;
+
+        let body = self.loop_body(&label)?;
+        let span = keyword.to(body.span());
+        let looping = Stmt::While { label, condition, body: Box::new(body), increment, span };
+
+        match initializer {
+            Some(initializer) => Ok(Stmt::Block { statements: vec![initializer, looping], span }),
+            None => Ok(looping),
+        }
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt> {
+        let keyword = self.previous().span;
+        self.consume(TokenEnum::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenEnum::RightParen, "Expect ')' after if condition.")?;
+
+        // The else goes with the closest if.
+        let then_branch = Box::new(self.nested(Self::statement)?);
+        let else_branch = if self.matching(vec![TokenEnum::Else]) {
+            Some(Box::new(self.nested(Self::statement)?))
+        } else {
+            None
+        };
+
+        let end = else_branch.as_ref().unwrap_or(&then_branch).span();
+        Ok(Stmt::If { condition, then_branch, else_branch, span: keyword.to(end) })
+    }
+
+    fn while_statement(&mut self, label: Option<Token>) -> Result<Stmt> {
+        let keyword = label.as_ref().map_or(self.previous().span, |label| label.span);
+        self.consume(TokenEnum::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenEnum::RightParen, "Expect ')' after condition.")?;
+        let body = self.loop_body(&label)?;
+
+        Ok(Stmt::While { label, condition, span: keyword.to(body.span()), body: Box::new(body), increment: None })
+    }
+
+    fn block(&mut self) -> Result<Stmt> {
+        let brace = self.previous().span;
+        let mut statements = Vec::new();
+
+        while !self.check(TokenEnum::RightBrace) && !self.is_at_end() {
+            statements.extend(self.recovering_declaration());
+        }
+
+        let end = self
+            .consume(TokenEnum::RightBrace, "Expect '}' after block.")
+            .map_err(|error| error.with_secondary(brace, "to match this '{'"))?;
+
+        Ok(Stmt::Block { statements, span: brace.to(end.span) })
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt> {
+        let keyword = self.previous().span;
+        let expression = self.expression()?;
+        let end = self.end_of_statement("Expect ';' after value.")?;
+
+        Ok(Stmt::Print { expression, span: keyword.to(end) })
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt> {
+        let keyword = self.previous();
+        let value = if self.check(TokenEnum::SemiColon) || self.is_at_end() {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let end = self.end_of_statement("Expect ';' after return value.")?;
+
+        Ok(Stmt::Return { span: keyword.span.to(end), keyword, value })
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt> {
+        let expression = self.expression()?;
+        let end = self.end_of_statement("Expect ';' after expression.")?;
+        let span = expression.span().to(end);
+
+        Ok(Stmt::Expression { expression, span })
+    }
+
+    // Consumes the ';' closing a statement and returns where the statement ends.
+    fn end_of_statement(&mut self, message: &str) -> Result<Span> {
+        if self.prompt && self.is_at_end() {
+            return Ok(self.previous().span);
+        }
+
+        Ok(self.consume(TokenEnum::SemiColon, message)?.span)
+    }
+
+    fn expression(&mut self) -> Result<Expression> {
+        self.nested(Self::comma)
+    }
+
+    // Lists separated by commas (call arguments) parse their items with `assignment`,
+    // one level below, so the comma there separates instead of being this operator.
+    fn comma(&mut self) -> Result<Expression> {
+        let depth = self.depth;
+        let mut expr = self.assignment()?;
 
         while self.matching(vec![TokenEnum::Comma]) {
+            self.deeper()?;
+            let right = self.assignment()?;
+            let span = expr.span().to(right.span());
+            expr = Expression::Comma { left: Box::new(expr), right: Box::new(right), span }
+        }
+
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    // The left side is parsed as an ordinary expression first, we only know it was
+    // an assignment target once we find the '='.
+    fn assignment(&mut self) -> Result<Expression> {
+        let expr = self.conditional()?;
+
+        if self.matching(vec![TokenEnum::Equal]) {
+            let equals = self.previous();
+            let value = self.nested(Self::assignment)?;
+            let span = expr.span().to(value.span());
+
+            return match expr {
+                Expression::Variable { name, .. } => Ok(Expression::Assign { name, value: Box::new(value), depth: None, span }),
+                Expression::Get { object, name, .. } => Ok(Expression::Set { object, name, value: Box::new(value), span }),
+                _ => Self::error(&error_code::INVALID_ASSIGNMENT_TARGET, equals, "Invalid assignment target.")
+                    .map_err(|error| error.with_secondary(expr.span(), "can't assign to this")),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    // The middle can be any expression, like inside parens. The else branch is another
+    // conditional, so `a ? b : c ? d : e` is `a ? b : (c ? d : e)`.
+    fn conditional(&mut self) -> Result<Expression> {
+        let condition = self.or()?;
+
+        if self.matching(vec![TokenEnum::Question]) {
+            let question = self.previous().span;
+            let then_branch = self.expression()?;
+            self.consume(TokenEnum::Colon, "Expect ':' after the then branch of a conditional expression.")
+                .map_err(|error| error.with_secondary(question, "to match this '?'"))?;
+            let else_branch = self.nested(Self::conditional)?;
+            let span = condition.span().to(else_branch.span());
+
+            return Ok(Expression::Conditional {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+                span,
+            });
+        }
+
+        Ok(condition)
+    }
+
+    fn or(&mut self) -> Result<Expression> {
+        let depth = self.depth;
+        let mut expr = self.and()?;
+
+        while self.matching(vec![TokenEnum::Or]) {
+            self.deeper()?;
+            let operator = self.previous();
+            let right = self.and()?;
+            let span = expr.span().to(right.span());
+            expr = Expression::Logical {
+                operator,
+                left: Box::new(expr),
+                right: Box::new(right),
+                span,
+            }
+        }
+
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expression> {
+        let depth = self.depth;
+        let mut expr = self.equality()?;
+
+        while self.matching(vec![TokenEnum::And]) {
+            self.deeper()?;
             let operator = self.previous();
             let right = self.equality()?;
-            expr = Expression::Binary { operator: operator, left: Box::new(expr), right: Box::new(right) }
+            let span = expr.span().to(right.span());
+            expr = Expression::Logical {
+                operator,
+                left: Box::new(expr),
+                right: Box::new(right),
+                span,
+            }
         }
 
+        self.depth = depth;
         Ok(expr)
     }
 
-    fn equality(&mut self) -> exception::Result<Expression> {
+    fn equality(&mut self) -> Result<Expression> {
+        let depth = self.depth;
         let mut expr = self.comparison()?;
 
         while self.matching(vec![TokenEnum::BangEqual, TokenEnum::EqualEqual]) {
+            self.deeper()?;
             let operator = self.previous();
             let right = self.comparison()?;
+            let span = expr.span().to(right.span());
             expr = Expression::Binary {
-                operator: operator,
+                operator,
                 left: Box::new(expr),
                 right: Box::new(right),
+                span,
             }
         }
 
+        self.depth = depth;
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> exception::Result<Expression> {
+    fn comparison(&mut self) -> Result<Expression> {
+        let depth = self.depth;
         let mut expr = self.term()?;
 
         while self.matching(vec![TokenEnum::Greater, TokenEnum::GreaterEqual, TokenEnum::Less, TokenEnum::LessEqual]) {
+            self.deeper()?;
             let operator = self.previous();
             let right = self.term()?;
+            let span = expr.span().to(right.span());
             expr = Expression::Binary {
-                operator: operator,
+                operator,
                 left: Box::new(expr),
                 right: Box::new(right),
+                span,
             }
         }
 
+        self.depth = depth;
         Ok(expr)
     }
 
-    fn term(&mut self) -> exception::Result<Expression> {
+    fn term(&mut self) -> Result<Expression> {
+        let depth = self.depth;
         let mut expr = self.factor()?;
 
         while self.matching(vec![TokenEnum::Plus, TokenEnum::Minus]) {
+            self.deeper()?;
             let operator = self.previous();
             let right = self.factor()?;
+            let span = expr.span().to(right.span());
             expr = Expression::Binary {
-                operator: operator,
+                operator,
                 left: Box::new(expr),
                 right: Box::new(right),
+                span,
             }
         }
 
+        self.depth = depth;
         Ok(expr)
     }
 
-    fn factor(&mut self) -> exception::Result<Expression> {
+    fn factor(&mut self) -> Result<Expression> {
+        let depth = self.depth;
         let mut expr = self.unary()?;
 
-        while self.matching(vec![TokenEnum::Star, TokenEnum::Slash]) {
+        while self.matching(vec![TokenEnum::Star, TokenEnum::Slash, TokenEnum::Percent]) {
+            self.deeper()?;
             let operator = self.previous();
             let right = self.unary()?;
+            let span = expr.span().to(right.span());
             expr = Expression::Binary {
-                operator: operator,
+                operator,
                 left: Box::new(expr),
                 right: Box::new(right),
+                span,
             }
         }
 
+        self.depth = depth;
         Ok(expr)
     }
 
-    fn unary(&mut self) -> exception::Result<Expression> {
+    fn unary(&mut self) -> Result<Expression> {
         if self.matching(vec![TokenEnum::Bang, TokenEnum::Minus]) {
             let operator = self.previous();
-            let right = self.unary()?;
+            let right = self.nested(Self::unary)?;
+            let span = operator.span.to(right.span());
             return Ok(Expression::Unary {
-                operator: operator,
+                operator,
                 right: Box::new(right),
+                span,
             });
         }
 
-        Ok(self.primary()?)
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expression> {
+        let depth = self.depth;
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.matching(vec![TokenEnum::LeftParen]) {
+                self.deeper()?;
+                expr = self.finish_call(expr)?;
+            } else if self.matching(vec![TokenEnum::Dot]) {
+                self.deeper()?;
+                let name = self.consume(TokenEnum::Identifier, "Expect property name after '.'.")?;
+                let span = expr.span().to(name.span);
+                expr = Expression::Get { object: Box::new(expr), name, span };
+            } else {
+                break;
+            }
+        }
+
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    // Arguments are parsed one level below the comma operator, there the comma separates them.
+    fn finish_call(&mut self, callee: Expression) -> Result<Expression> {
+        let open = self.previous().span;
+        let mut arguments = Vec::new();
+
+        if !self.check(TokenEnum::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let message = format!("Can't have more than {} arguments.", MAX_ARGUMENTS);
+                    self.errors.push(Self::error_at(&error_code::TOO_MANY_ARGUMENTS, &self.peek(), &message));
+                }
+
+                arguments.push(self.nested(Self::assignment)?);
+
+                if !self.matching(vec![TokenEnum::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self
+            .consume(TokenEnum::RightParen, "Expect ')' after arguments.")
+            .map_err(|error| error.with_secondary(open, "to match this '('"))?;
+        let span = callee.span().to(paren.span);
+
+        Ok(Expression::Call { callee: Box::new(callee), paren, arguments, span })
     }
 
-    fn primary(&mut self) -> exception::Result<Expression> {
+    fn primary(&mut self) -> Result<Expression> {
+        let span = self.peek().span;
         match self.peek().token_type {
             TokenEnum::True => {
                 self.advance();
-                return Ok(Expression::Literal { value: crate::token::Literal::Bool(true) });
+                Ok(Expression::Literal { value: crate::token::Literal::Bool(true), span })
             },
             TokenEnum::False => {
                 self.advance();
-                return Ok(Expression::Literal { value: crate::token::Literal::Bool(false) });
+                Ok(Expression::Literal { value: crate::token::Literal::Bool(false), span })
             },
             TokenEnum::Null => {
                 self.advance();
-                return Ok(Expression::Literal { value: crate::token::Literal::None });
+                Ok(Expression::Literal { value: crate::token::Literal::None, span })
             },
             TokenEnum::Number | TokenEnum::String => {
                 self.advance();
-                return Ok(Expression::Literal { value: self.previous().literal });
+                Ok(Expression::Literal { value: self.previous().literal, span })
+            }
+            TokenEnum::Identifier => {
+                Ok(Expression::Variable { name: self.advance(), depth: None, span })
+            }
+            TokenEnum::Super => {
+                let keyword = self.advance();
+                self.consume(TokenEnum::Dot, "Expect '.' after 'super'.")?;
+                let method = self.consume(TokenEnum::Identifier, "Expect superclass method name.")?;
+                Ok(Expression::Super { span: span.to(method.span), keyword, method, depth: None })
+            }
+            TokenEnum::This => {
+                Ok(Expression::This { keyword: self.advance(), depth: None, span })
             }
             TokenEnum::LeftParen => {
                 self.advance();
                 let expr = self.expression()?;
-                self.consume(TokenEnum::RightParen, "Except ')' after expression.")?;
-                return Ok(Expression::Grouping { expression: Box::new(expr) });
+                let paren = self
+                    .consume(TokenEnum::RightParen, "Except ')' after expression.")
+                    .map_err(|error| error.with_secondary(span, "to match this '('"))?;
+                Ok(Expression::Grouping { expression: Box::new(expr), span: span.to(paren.span) })
             }
             _ => {
-                return Self::error(self.peek().clone(), "Expect expression.");
+                Self::error(&error_code::EXPECTED_EXPRESSION, self.peek().clone(), "Expect expression.")
             }
         }
 
     }
 
     // This consumes the token and returns true. Otherwise, it returns false and leaves the current token alone.
+    // Runs `parse` one level deeper. Everything it adds to the depth is undone after,
+    // even when it fails halfway through an operator chain.
+    fn nested<T>(&mut self, parse: fn(&mut Self) -> Result<T>) -> Result<T> {
+        let depth = self.depth;
+        self.deeper()?;
+        let result = parse(self);
+        self.depth = depth;
+        result
+    }
+
+    // One more level, failing once the input is nested MAX_NESTING levels. Operator and
+    // call chains are parsed in loops but make trees as deep as they are long, so every
+    // operator counts too.
+    fn deeper(&mut self) -> Result<()> {
+        if self.depth >= MAX_NESTING {
+            let message = format!("Nested too deeply, more than {} levels.", MAX_NESTING);
+            let error = Self::error(&error_code::NESTING_TOO_DEEP, self.peek(), &message);
+            self.too_deep = true;
+            self.current = self.tokens.len() - 1;
+            return error;
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
     fn matching(&mut self, types: Vec<TokenEnum>) -> bool {
         for token_type in types.into_iter() {
             if self.check(token_type) {
@@ -189,6 +788,11 @@ impl Parser {
         self.peek().token_type == token_type
     }
 
+    // Like check, but one token further.
+    fn check_next(&self, token_type: TokenEnum) -> bool {
+        self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == token_type)
+    }
+
     fn advance(&mut self) -> Token {
         if !self.is_at_end() {
             self.current += 1;
@@ -198,7 +802,7 @@ impl Parser {
     }
 
     fn is_at_end(&self) -> bool {
-        return self.peek().token_type == TokenEnum::EOF;
+        self.peek().token_type == TokenEnum::EOF
     }
 
     // returns the current token we have yet to consume
@@ -211,22 +815,27 @@ impl Parser {
         self.tokens.get(self.current - 1).unwrap().clone() // Problably adding a lifetime in Token<'a> ?
     }
 
-    fn error<T>(token: Token, message: &str) -> exception::Result<T> {
+    fn error<T>(code: &'static ErrorCode, token: Token, message: &str) -> Result<T> {
+        Err(Self::error_at(code, &token, message))
+    }
+
+    fn error_at(code: &'static ErrorCode, token: &Token, message: &str) -> ParseError {
         if token.token_type == TokenEnum::EOF {
-            return exception::Exception::error(token.line, " at end", message);
+            ParseError::new(code, token.span, " at end", message)
         }
         else {
             let mut where_r = String::from(" at '");
             where_r.push_str(token.lexeme.as_str());
-            return exception::Exception::error(token.line, where_r.as_str(), message);
+            ParseError::new(code, token.span, where_r.as_str(), message)
         }
     }
 
+    // Discards tokens until what looks like the start of the next statement.
     fn synchronize(&mut self) {
         self.advance();
         while !self.is_at_end() {
             if self.previous().token_type == TokenEnum::SemiColon {
-                return ();
+                return ;
             }
 
             match self.peek().token_type {
@@ -237,7 +846,7 @@ impl Parser {
                 | TokenEnum::If
                 | TokenEnum::While
                 | TokenEnum::Print
-                | TokenEnum::Return => (),
+                | TokenEnum::Return => return,
                 _ => ()
             }
 
@@ -245,18 +854,18 @@ impl Parser {
         }
     }
 
-    fn consume(&mut self, token_type: TokenEnum, message: &str) -> exception::Result<Token> {
+    fn consume(&mut self, token_type: TokenEnum, message: &str) -> Result<Token> {
         if self.check(token_type) {
             return Ok(self.advance());
         }
 
-        Self::error(self.peek().clone(), message)
+        Self::error(&error_code::EXPECTED_TOKEN, self.peek().clone(), message)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{scanner::Scanner};
+    use crate::{scanner::Scanner, span::Span};
 
     use super::*;
 
@@ -290,6 +899,373 @@ mod tests {
         assert!(parser.is_ok());
     }
 
+    #[test]
+    fn spans() {
+        let mut scan = Scanner::new("-(10 +\n 2) * 3");
+        let tokens = scan.scan_tokens().unwrap();
+        let expression = Parser::new(tokens).parser().unwrap();
+
+        assert_eq!(expression.span(), Span::new(0, 14, 1, 1));
+
+        let Expression::Binary { left, right, .. } = expression else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!(left.span(), Span::new(0, 10, 1, 1));
+        assert_eq!(right.span(), Span::new(13, 14, 2, 7));
+    }
+
+    #[test]
+    fn error_span() {
+        let mut scan = Scanner::new("1 +\n  )");
+        let tokens = scan.scan_tokens().unwrap();
+        let error = Parser::new(tokens).parser().unwrap_err();
+
+        assert_eq!(error.span(), Span::new(6, 7, 2, 3));
+        assert_eq!(error.code(), &error_code::EXPECTED_EXPRESSION);
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let mut scan = Scanner::new("(1 + 2;\n3 + ;\n4 * 2;\n)");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        let found: Vec<(u32, &str)> = errors.iter().map(|error| (error.line(), error.message())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, "Except ')' after expression."),
+                (2, "Expect expression."),
+                (4, "Expect expression."),
+            ]
+        );
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].print().unwrap(), "(* 4 2)");
+    }
+
+    #[test]
+    fn synchronizes_on_keywords() {
+        let mut scan = Scanner::new("1 + + print 2; 3 +");
+        let tokens = scan.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let (statements, errors) = parser.parse();
+
+        // Stops at `print` instead of swallowing it with the broken expression.
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].span(), Span::new(18, 18, 1, 19));
+        assert_eq!(statements[0].print().unwrap(), "(print 2)");
+    }
+
+    #[test]
+    fn statements() {
+        let mut scan = Scanner::new("print 1 + 2;\n3;\nprint \"done\";");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        assert!(errors.is_empty());
+        let printed: Vec<String> = statements.iter().map(|statement| statement.print().unwrap()).collect();
+        assert_eq!(printed, vec!["(print (+ 1 2))", "3", "(print done)"]);
+        assert_eq!(statements[0].span(), Span::new(0, 12, 1, 1));
+    }
+
+    #[test]
+    fn statements_need_a_semicolon() {
+        let mut scan = Scanner::new("print 1 print 2");
+        let tokens = scan.scan_tokens().unwrap();
+        let (_, errors) = Parser::new(tokens).parse();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "Expect ';' after value.");
+    }
+
+    #[test]
+    fn prompt_can_leave_out_the_last_semicolon() {
+        let tokens = Scanner::new("print 1; 1 + 2").scan_tokens().unwrap();
+
+        let (_, errors) = Parser::new(tokens.clone()).parse();
+        assert_eq!(errors[0].message(), "Expect ';' after expression.");
+
+        let (statements, errors) = Parser::new(tokens).prompt(true).parse();
+        assert!(errors.is_empty());
+        assert_eq!(statements[1].print().unwrap(), "(+ 1 2)");
+    }
+
+    #[test]
+    fn variables() {
+        let mut scan = Scanner::new("var a;\nvar b = 1 + 2;\na = b = 3;");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        assert!(errors.is_empty());
+        let printed: Vec<String> = statements.iter().map(|statement| statement.print().unwrap()).collect();
+        assert_eq!(printed, vec!["(var a)", "(var b (+ 1 2))", "(= a (= b 3))"]);
+        assert_eq!(statements[1].span(), Span::new(7, 21, 2, 1));
+    }
+
+    #[test]
+    fn invalid_assignment_target() {
+        let mut scan = Scanner::new("a + b = 3;\nvar = 1;");
+        let tokens = scan.scan_tokens().unwrap();
+        let (_, errors) = Parser::new(tokens).parse();
+
+        let found: Vec<(&str, Span)> = errors.iter().map(|error| (error.message(), error.span())).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Invalid assignment target.", Span::new(6, 7, 1, 7)),
+                ("Expect variable name.", Span::new(15, 16, 2, 5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        let mut scan = Scanner::new("{ var a = 1; { print a; } }");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        assert!(errors.is_empty());
+        assert_eq!(statements[0].print().unwrap(), "(block (var a 1) (block (print a)))");
+        assert_eq!(statements[0].span(), Span::new(0, 27, 1, 1));
+    }
+
+    #[test]
+    fn errors_inside_blocks() {
+        let mut scan = Scanner::new("{\n  1 +;\n  print 2;\n  var;\n}\n{ print 3;");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        let found: Vec<(u32, &str)> = errors.iter().map(|error| (error.line(), error.message())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, "Expect expression."),
+                (4, "Expect variable name."),
+                (6, "Expect '}' after block."),
+            ]
+        );
+        assert_eq!(statements[0].print().unwrap(), "(block (print 2))");
+    }
+
+    #[test]
+    fn control_flow() {
+        let mut scan = Scanner::new("if (a or b and c) print 1; else if (d) print 2;\nwhile (x) x = false;");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        assert!(errors.is_empty());
+        let printed: Vec<String> = statements.iter().map(|statement| statement.print().unwrap()).collect();
+        assert_eq!(printed, vec!["(if (or a (and b c)) (print 1) (if d (print 2)))", "(while x (= x false))"]);
+        assert_eq!(statements[0].span(), Span::new(0, 47, 1, 1));
+    }
+
+    #[test]
+    fn dangling_else() {
+        let mut scan = Scanner::new("if (a) if (b) print 1; else print 2;");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, _) = Parser::new(tokens).parse();
+
+        assert_eq!(statements[0].print().unwrap(), "(if a (if b (print 1) (print 2)))");
+    }
+
+    #[test]
+    fn for_becomes_while() {
+        let mut scan = Scanner::new("for (var i = 0; i < 3; i = i + 1) print i;\nfor (;;) print 1;");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            statements[0].print().unwrap(),
+            "(block (var i 0) (while (< i 3) (print i) (= i (+ i 1))))"
+        );
+        assert_eq!(statements[1].print().unwrap(), "(while true (print 1))");
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let mut scan = Scanner::new("outer: for (;;) { while (true) { if (a) break outer; continue; } break; }");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            statements[0].print().unwrap(),
+            "(label outer (while true (block (while true (block (if a (break outer)) (continue))) (break))))"
+        );
+    }
+
+    #[test]
+    fn jumps_outside_loops() {
+        let mut scan = Scanner::new("break;\nwhile (true) { continue nope; }\n{ continue; }\nlabel: print 1;");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        let found: Vec<(&str, Span)> = errors.iter().map(|error| (error.message(), error.span())).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Can't use 'break' outside of a loop.", Span::new(0, 5, 1, 1)),
+                ("There's no loop labelled 'nope' around this 'continue'.", Span::new(31, 35, 2, 25)),
+                ("Can't use 'continue' outside of a loop.", Span::new(41, 49, 3, 3)),
+                ("Expect a loop after a label.", Span::new(60, 65, 4, 8)),
+            ]
+        );
+        assert_eq!(errors[0].code(), &error_code::JUMP_OUTSIDE_LOOP);
+        assert_eq!(statements.len(), 3);
+    }
+
+    #[test]
+    fn functions() {
+        let mut scan = Scanner::new("fun add(a, b) { return a + b; }\nprint add(1, 2)(3)();\nfun nothing() { return; }");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        assert!(errors.is_empty());
+        let printed: Vec<String> = statements.iter().map(|statement| statement.print().unwrap()).collect();
+        assert_eq!(
+            printed,
+            vec![
+                "(fun add (a b) (return (+ a b)))",
+                "(print (call (call (call add 1 2) 3)))",
+                "(fun nothing () (return))",
+            ]
+        );
+        assert_eq!(statements[0].span(), Span::new(0, 31, 1, 1));
+    }
+
+    #[test]
+    fn classes() {
+        let mut scan = Scanner::new("class Point { init(x) { this.x = x; } norm() { return this.x; } }\npoint.next.x = point.norm();");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        assert!(errors.is_empty());
+        let printed: Vec<String> = statements.iter().map(|statement| statement.print().unwrap()).collect();
+        assert_eq!(
+            printed,
+            vec![
+                "(class Point (fun init (x) (=. x this x)) (fun norm () (return (. x this))))",
+                "(=. x (. next point) (call (. norm point)))",
+            ]
+        );
+        assert_eq!(statements[0].span(), Span::new(0, 65, 1, 1));
+    }
+
+    #[test]
+    fn inheritance() {
+        let mut scan = Scanner::new("class B < A { f() { return super.f(); } }");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        assert!(errors.is_empty());
+        assert_eq!(statements[0].print().unwrap(), "(class B < A (fun f () (return (call (super f)))))");
+    }
+
+    #[test]
+    fn call_arguments_are_not_comma_expressions() {
+        let mut scan = Scanner::new("f((1, 2), 3);");
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, _) = Parser::new(tokens).parse();
+
+        assert_eq!(statements[0].print().unwrap(), "(call f (group (, 1 2)) 3)");
+    }
+
+    #[test]
+    fn loops_do_not_reach_into_functions() {
+        let mut scan = Scanner::new("while (true) { fun f() { break; } }");
+        let tokens = scan.scan_tokens().unwrap();
+        let (_, errors) = Parser::new(tokens).parse();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code(), &error_code::JUMP_OUTSIDE_LOOP);
+    }
+
+    #[test]
+    fn too_many_arguments() {
+        let arguments = vec!["1"; 256].join(", ");
+        let source = format!("f({});", arguments);
+        let mut scan = Scanner::new(&source);
+        let tokens = scan.scan_tokens().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "Can't have more than 255 arguments.");
+        assert_eq!(statements.len(), 1);
+    }
+
+    #[test]
+    fn nesting_too_deep() {
+        // Test threads get a small stack, the binary parses on one of STACK_SIZE.
+        let thread = std::thread::Builder::new().stack_size(crate::interpreter::STACK_SIZE);
+        let handle = thread.spawn(|| {
+            let deep = 3000;
+            let sources = [
+                format!("var x = {}1{};", "(".repeat(deep), ")".repeat(deep)),
+                format!("var x = {}1;", "-".repeat(deep)),
+                format!("{}{}", "{".repeat(deep), "}".repeat(deep)),
+                format!("{}print 1;", "if (true) ".repeat(deep)),
+                format!("var x = {}1{};", "(".repeat(200), ")".repeat(200)),
+            ];
+
+            sources.map(|source| {
+                let mut scan = Scanner::new(&source);
+                let (_, errors) = Parser::new(scan.scan_tokens().unwrap()).parse();
+                errors.iter().map(|error| error.code().code).collect::<Vec<_>>()
+            })
+        });
+
+        let nested = vec![error_code::NESTING_TOO_DEEP.code];
+        assert_eq!(handle.unwrap().join().unwrap(), [nested.clone(), nested.clone(), nested.clone(), nested, vec![]]);
+    }
+
+    #[test]
+    fn long_chains() {
+        let thread = std::thread::Builder::new().stack_size(crate::interpreter::STACK_SIZE);
+        let handle = thread.spawn(|| {
+            let long = 3000;
+            let sources = [
+                format!("print {};", vec!["1"; long].join(" + ")),
+                format!("print {};", vec!["true"; long].join(" and ")),
+                format!("class A {{ init() {{ this.b = this; }} }}\nprint A(){};", ".b".repeat(long)),
+                format!("fun f() {{ return f; }}\nprint f{};", "()".repeat(long)),
+                format!("print {};", vec!["1"; 200].join(" + ")),
+            ];
+
+            sources.map(|source| {
+                let mut interpreter = crate::interpreter::Interpreter::with_output(Vec::new());
+                match crate::run(&source, &mut interpreter) {
+                    Ok(_) => String::from_utf8(interpreter.output().clone()).unwrap(),
+                    Err(error) => error.diagnostics()[0].code.unwrap().to_string(),
+                }
+            })
+        });
+
+        let nested = error_code::NESTING_TOO_DEEP.code.to_string();
+        assert_eq!(handle.unwrap().join().unwrap(), [nested.clone(), nested.clone(), nested.clone(), nested, "200\n".to_string()]);
+    }
+
+    #[test]
+    fn conditional() {
+        let mut scan = Scanner::new("a = b ? 1 : c ? 2, 3 : 4 or 5, 6");
+        let tokens = scan.scan_tokens().unwrap();
+        let expression = Parser::new(tokens).parser().unwrap();
+
+        assert_eq!(expression.print().unwrap(), "(, (= a (?: b 1 (?: c (, 2 3) (or 4 5)))) 6)");
+        assert_eq!(expression.reverse_polish_notation().unwrap(), "a b 1 c 2 3 , 4 5 or ?: ?: = 6 ,");
+    }
+
+    #[test]
+    fn conditional_needs_a_colon() {
+        let mut scan = Scanner::new("a ? b;");
+        let tokens = scan.scan_tokens().unwrap();
+        let (_, errors) = Parser::new(tokens).parse();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "Expect ':' after the then branch of a conditional expression.");
+        assert_eq!(errors[0].diagnostic().labels[1].span, Span::new(2, 3, 1, 3));
+    }
+
     #[test]
     fn expressions() {
         let mut scan = Scanner::new("(10 + 2) / 2");
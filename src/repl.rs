@@ -0,0 +1,483 @@
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use totx::{
+    diagnostic::{Diagnostic, Renderer},
+    exception::{BaseException, Error},
+    interpreter::Interpreter,
+    parser::Parser,
+    scanner::Scanner,
+    token::Token,
+    Program,
+};
+
+const HISTORY_FILE: &str = ".totx_history";
+const HISTORY_LIMIT: usize = 1000;
+
+const HELP: &str = "\
+:tokens <code>  show the tokens scanned from <code>
+:ast <code>     show the tree parsed from <code>
+:load <file>    run <file> in this session
+:reset          forget everything defined so far
+:history        show the previous entries
+:help           show this message
+:quit           leave the prompt";
+
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    Continue,
+    Quit,
+}
+
+pub struct Repl<O: Write, E: Write> {
+    // Owns the output, so what `print` writes and what the prompt shows go to the same place.
+    interpreter: Interpreter<O>,
+    history: History,
+    // Lines of an entry that isn't complete yet (an open paren, brace or string).
+    buffer: String,
+    err: E,
+    color: bool,
+}
+
+impl<O: Write, E: Write> Repl<O, E> {
+    pub fn new(out: O, err: E, history: History) -> Self {
+        Self {
+            interpreter: Interpreter::with_output(out),
+            history,
+            buffer: String::new(),
+            err,
+            color: false,
+        }
+    }
+
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    fn out(&mut self) -> &mut O {
+        self.interpreter.output_mut()
+    }
+
+    pub fn prompt(&self) -> &'static str {
+        if self.buffer.is_empty() {
+            "> "
+        } else {
+            "... "
+        }
+    }
+
+    // Feeds one line typed by the user. Nothing runs until the entry is complete,
+    // an empty line forces it to run anyway so the user can see what's wrong.
+    pub fn feed(&mut self, line: &str) -> Status {
+        let line = line.trim_end_matches(['\n', '\r']);
+
+        if self.buffer.is_empty() {
+            if line.trim().is_empty() {
+                return Status::Continue;
+            }
+
+            if let Some(command) = line.trim_start().strip_prefix(':') {
+                self.history.push(line);
+                return self.meta_command(command);
+            }
+        }
+
+        let force = !self.buffer.is_empty() && line.trim().is_empty();
+        if !force {
+            if !self.buffer.is_empty() {
+                self.buffer.push('\n');
+            }
+            self.buffer.push_str(line);
+        }
+
+        if !force && is_incomplete(&self.buffer) {
+            return Status::Continue;
+        }
+
+        let entry = std::mem::take(&mut self.buffer);
+        self.history.push(&entry);
+        self.run(&entry, totx::compile_entry);
+
+        Status::Continue
+    }
+
+    fn meta_command(&mut self, command: &str) -> Status {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command.trim(), ""),
+        };
+
+        match (name, argument) {
+            ("quit" | "q", _) => return Status::Quit,
+            ("help" | "h", _) => {
+                let _ = writeln!(self.out(), "{}", HELP);
+            }
+            ("reset", _) => {
+                self.interpreter.reset();
+                let _ = writeln!(self.out(), "Session reset.");
+            }
+            ("history", _) => {
+                for (index, entry) in self.history.entries().iter().enumerate() {
+                    let _ = writeln!(self.interpreter.output_mut(), "{:>4}  {}", index + 1, entry);
+                }
+            }
+            ("tokens", code) if !code.is_empty() => self.show_tokens(code),
+            ("ast", code) if !code.is_empty() => self.show_ast(code),
+            ("load", path) if !path.is_empty() => match fs::read_to_string(path) {
+                Ok(source) => self.run(&source, totx::compile),
+                Err(error) => {
+                    let _ = writeln!(self.err, "Can't read '{}': {}", path, error);
+                }
+            },
+            ("tokens" | "ast" | "load", _) => {
+                let _ = writeln!(self.err, "Usage: :{} <{}>", name, if name == "load" { "file" } else { "code" });
+            }
+            _ => {
+                let _ = writeln!(self.err, "Unknown command ':{}', try :help", name);
+            }
+        }
+
+        Status::Continue
+    }
+
+    fn show_tokens(&mut self, code: &str) {
+        if let Some(tokens) = self.scan(code) {
+            for token in tokens {
+                let _ = writeln!(self.out(), "{}", token);
+            }
+        }
+    }
+
+    fn show_ast(&mut self, code: &str) {
+        let Some(tokens) = self.scan(code) else {
+            return;
+        };
+
+        let (statements, errors) = Parser::new(tokens).prompt(true).parse();
+        if !errors.is_empty() {
+            let diagnostics: Vec<Diagnostic> = errors.iter().map(|error| error.diagnostic()).collect();
+            return self.report(code, &diagnostics);
+        }
+
+        for statement in statements {
+            let ast = statement.print().unwrap_or_default();
+            let _ = writeln!(self.out(), "{}", ast);
+        }
+    }
+
+    // Runs an entry or a loaded file, showing the value when it ends with an expression.
+    // Only entries can leave out the last ';', so they compile with `compile_entry`.
+    fn run(&mut self, source: &str, compile: fn(&str) -> Result<Program, Error>) {
+        let program = match compile(source) {
+            Ok(program) => program,
+            Err(error) => return self.report(source, &error.diagnostics()),
+        };
+        self.report(source, &program.warnings);
+
+        match self.interpreter.interpret(&program) {
+            Ok(Some(value)) => {
+                let _ = writeln!(self.out(), "{}", value);
+            }
+            Ok(None) => (),
+            Err(error) => self.report(source, &[error.diagnostic()]),
+        }
+    }
+
+    fn scan(&mut self, source: &str) -> Option<Vec<Token>> {
+        match Scanner::new(source).scan_tokens() {
+            Ok(tokens) => Some(tokens),
+            Err(errors) => {
+                let diagnostics: Vec<Diagnostic> = errors.iter().map(|error| error.diagnostic()).collect();
+                self.report(source, &diagnostics);
+                None
+            }
+        }
+    }
+
+    fn report(&mut self, source: &str, diagnostics: &[Diagnostic]) {
+        let renderer = Renderer::new(source, "<prompt>").color(self.color);
+        for diagnostic in diagnostics {
+            let _ = write!(self.err, "{}", renderer.render(diagnostic));
+        }
+    }
+}
+
+// True while the source still has an open paren, brace, string or block comment,
+// meaning the user wants to keep typing on the next line.
+pub fn is_incomplete(source: &str) -> bool {
+    let mut depth: i32 = 0;
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            '"' => {
+                let closed = chars.by_ref().any(|c| c == '"');
+                if !closed {
+                    return true;
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                // Skip the comment, the input may continue on the next line.
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    if c == '*' && chars.peek() == Some(&'/') {
+                        chars.next();
+                        closed = true;
+                        break;
+                    }
+                }
+                if !closed {
+                    return true;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    depth > 0
+}
+
+// Entries typed in the prompt, saved one per line. Multi-line entries get their
+// newlines escaped so they come back as a single entry.
+#[derive(Debug, Default)]
+pub struct History {
+    path: Option<PathBuf>,
+    entries: Vec<String>,
+}
+
+impl History {
+    // $TOTX_HISTORY wins, otherwise ~/.totx_history. Without any of them nothing is saved.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("TOTX_HISTORY") {
+            return Some(PathBuf::from(path));
+        }
+
+        env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+    }
+
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| content.lines().map(unescape).collect())
+            .unwrap_or_default();
+
+        Self { path, entries }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    pub fn push(&mut self, entry: &str) {
+        if self.entries.last().is_some_and(|last| last == entry) {
+            return;
+        }
+
+        self.entries.push(entry.to_string());
+        if self.entries.len() > HISTORY_LIMIT {
+            self.entries.remove(0);
+        }
+
+        // Losing the history isn't worth interrupting the session for.
+        let _ = self.save();
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut content = String::new();
+        for entry in &self.entries {
+            content.push_str(&escape(entry));
+            content.push('\n');
+        }
+
+        fs::write(path, content)
+    }
+}
+
+fn escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut entry = String::new();
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                chars.next();
+                entry.push('\n');
+            }
+            ('\\', Some('\\')) => {
+                chars.next();
+                entry.push('\\');
+            }
+            _ => entry.push(c),
+        }
+    }
+
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repl() -> Repl<Vec<u8>, Vec<u8>> {
+        Repl::new(Vec::new(), Vec::new(), History::default())
+    }
+
+    fn output(repl: &Repl<Vec<u8>, Vec<u8>>) -> String {
+        String::from_utf8(repl.interpreter.output().clone()).unwrap()
+    }
+
+    #[test]
+    fn prints_expression_results() {
+        let mut repl = repl();
+        repl.feed("1 + 2\n");
+        repl.feed("\"te\" + \"st\"\n");
+
+        assert_eq!(output(&repl), "3\ntest\n");
+    }
+
+    #[test]
+    fn prints_null() {
+        let mut repl = repl();
+        repl.feed("null\n");
+
+        assert_eq!(output(&repl), "null\n");
+    }
+
+    #[test]
+    fn print_statements_are_not_echoed() {
+        let mut repl = repl();
+        repl.feed("print 1; print 2;\n");
+        repl.feed("print 3; 4\n");
+
+        assert_eq!(output(&repl), "1\n2\n3\n4\n");
+    }
+
+    #[test]
+    fn keeps_variables_between_entries() {
+        let mut repl = repl();
+        repl.feed("var a = 1;\n");
+        repl.feed("a = a + 1;\n");
+        repl.feed("print a;\n");
+        repl.feed(":reset\n");
+        repl.feed("a\n");
+
+        assert_eq!(output(&repl), "2\n2\nSession reset.\n");
+        assert!(String::from_utf8(repl.err).unwrap().contains("Undefined variable 'a'."));
+    }
+
+    #[test]
+    fn keeps_reading_unclosed_input() {
+        let mut repl = repl();
+
+        assert_eq!(repl.feed("(1 +\n"), Status::Continue);
+        assert_eq!(repl.prompt(), "... ");
+        assert_eq!(output(&repl), "");
+
+        repl.feed("2) * 3\n");
+        assert_eq!(repl.prompt(), "> ");
+        assert_eq!(output(&repl), "9\n");
+    }
+
+    #[test]
+    fn empty_line_runs_incomplete_input() {
+        let mut repl = repl();
+        repl.feed("(1 +\n");
+        repl.feed("\n");
+
+        assert_eq!(repl.prompt(), "> ");
+        assert!(!repl.err.is_empty());
+    }
+
+    #[test]
+    fn incomplete_input() {
+        assert!(is_incomplete("(1 + 2"));
+        assert!(is_incomplete("{"));
+        assert!(is_incomplete(r#""unclosed"#));
+        assert!(is_incomplete("1 /* still"));
+        assert!(!is_incomplete("(1 + 2)"));
+        assert!(!is_incomplete(r#""(""#));
+        assert!(!is_incomplete("1 // ("));
+        assert!(!is_incomplete("1)"));
+    }
+
+    #[test]
+    fn meta_commands() {
+        let mut repl = repl();
+        repl.feed(":ast (1 + 2) * 3\n");
+        repl.feed(":tokens 1\n");
+
+        assert_eq!(output(&repl), "(* (group (+ 1 2)) 3)\nNumber - 1 - Number(1) - 1:1\nEOF -  - None - 1:2\n");
+        assert_eq!(repl.feed(":quit\n"), Status::Quit);
+    }
+
+    #[test]
+    fn shows_errors_with_the_source() {
+        let mut repl = repl();
+        repl.feed("1 + \"x\"\n");
+
+        assert_eq!(
+            String::from_utf8(repl.err).unwrap(),
+            "\
+error[T0201]: Must be all string or number for PLUS (+)
+ --> <prompt>:1:3
+  |
+1 | 1 + \"x\"
+  |   ^
+  | - this has type integer
+  |     --- this has type string
+"
+        );
+    }
+
+    #[test]
+    fn warnings_do_not_stop_the_entry() {
+        let mut repl = repl();
+        repl.feed("{ var unused = 1; print 2; }\n");
+
+        assert_eq!(output(&repl), "2\n");
+        assert!(String::from_utf8(repl.err).unwrap().starts_with("warning[T0300]: Unused local variable 'unused'."));
+    }
+
+    #[test]
+    fn unknown_meta_command() {
+        let mut repl = repl();
+        repl.feed(":nope\n");
+
+        assert!(String::from_utf8(repl.err).unwrap().contains("Unknown command"));
+    }
+
+    #[test]
+    fn history_round_trip() {
+        let path = env::temp_dir().join(format!("totx_history_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut history = History::load(Some(path.clone()));
+        history.push("1 + 1");
+        history.push("(1 +\n2)");
+        history.push("(1 +\n2)");
+
+        let history = History::load(Some(path.clone()));
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(history.entries(), ["1 + 1", "(1 +\n2)"]);
+    }
+}
@@ -0,0 +1,431 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    ast::{Expression, FunctionDeclaration, Stmt},
+    diagnostic::Diagnostic,
+    error_code,
+    exception::ResolveError,
+    span::Span,
+    token::Token,
+};
+
+// Runs between the parser and the interpreter. Walks the tree once, working out for
+// every variable how many scopes out its declaration is, so the interpreter goes
+// straight there instead of searching by name. Doing it before running also means a
+// closure keeps pointing at the variable it saw when declared, even if a later
+// declaration shadows it.
+//
+// It also finds the mistakes that only need the scopes to be seen: reading a local in
+// its own initializer, declaring a name twice in a scope, `return` outside a function,
+// `this` outside a class, `super` outside a subclass, a class inheriting from itself,
+// returning a value from `init`, and (as warnings) locals nobody reads.
+//
+// Globals are left unresolved (depth None), they're looked up by name at runtime so
+// the prompt can keep redefining them.
+pub struct Resolver {
+    // One map per block or function being resolved, innermost last. Empty at the top level.
+    scopes: Vec<HashMap<String, Local>>,
+    // What the code being resolved is inside of, to know where `return` and `this` are allowed.
+    function: FunctionKind,
+    class: ClassKind,
+    errors: Vec<ResolveError>,
+    warnings: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassKind {
+    None,
+    Class,
+    Subclass,
+}
+
+struct Local {
+    span: Span,
+    // False between the declaration and the end of its initializer.
+    defined: bool,
+    used: bool,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self { scopes: Vec::new(), function: FunctionKind::None, class: ClassKind::None, errors: Vec::new(), warnings: Vec::new() }
+    }
+
+    // Fills the depth of every variable in `statements`. Gives back the errors, the
+    // script shouldn't run when there are any, and the warnings.
+    pub fn resolve(mut self, statements: &mut [Stmt]) -> (Vec<ResolveError>, Vec<Diagnostic>) {
+        self.statements(statements);
+        (self.errors, self.warnings)
+    }
+
+    fn statements(&mut self, statements: &mut [Stmt]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &mut Stmt) {
+        match statement {
+            Stmt::Block { statements, .. } => {
+                self.begin_scope();
+                self.statements(statements);
+                self.end_scope();
+            }
+            Stmt::Var { name, initializer, .. } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                self.define(name);
+            }
+            // Defined before the body so the function can call itself. When the caller kept a
+            // clone of the statements the declaration is shared, it's copied so only ours changes.
+            Stmt::Function { declaration } => {
+                let declaration = Rc::make_mut(declaration);
+                self.declare(&declaration.name);
+                self.define(&declaration.name);
+                self.function(declaration, FunctionKind::Function);
+            }
+            // Methods get a scope of their own around them with `this`, like the one
+            // binding makes at runtime, and one more outside it with `super` in subclasses.
+            Stmt::Class { name, superclass, methods, .. } => {
+                let enclosing = std::mem::replace(&mut self.class, ClassKind::Class);
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if let Expression::Variable { name: parent, .. } = superclass {
+                        if parent.lexeme == name.lexeme {
+                            let error = ResolveError::new(&error_code::INHERIT_FROM_SELF, parent.span, "Resolver.rs", "A class can't inherit from itself.")
+                                .with_secondary(name.span, "this class");
+                            self.errors.push(error);
+                        }
+                    }
+
+                    self.class = ClassKind::Subclass;
+                    self.expression(superclass);
+                    self.begin_scope();
+                    self.define_implicit("super", name.span);
+                }
+
+                self.begin_scope();
+                self.define_implicit("this", name.span);
+
+                for method in methods {
+                    let kind = if method.name.lexeme == "init" { FunctionKind::Initializer } else { FunctionKind::Method };
+                    let method = Rc::make_mut(method);
+                    self.function(method, kind);
+                }
+
+                self.end_scope();
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+                self.class = enclosing;
+            }
+            Stmt::Expression { expression, .. } | Stmt::Print { expression, .. } => self.expression(expression),
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::While { condition, body, increment, .. } => {
+                self.expression(condition);
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                }
+            }
+            Stmt::Return { keyword, value, .. } => {
+                if self.function == FunctionKind::None {
+                    self.errors.push(ResolveError::new(&error_code::RETURN_OUTSIDE_FUNCTION, keyword.span, "Resolver.rs", "Can't return from top-level code."));
+                }
+                if let Some(value) = value {
+                    if self.function == FunctionKind::Initializer {
+                        let error = ResolveError::new(&error_code::RETURN_VALUE_FROM_INITIALIZER, value.span(), "Resolver.rs", "Can't return a value from an initializer.")
+                            .with_help("`init` always gives back the instance, use a bare `return;`");
+                        self.errors.push(error);
+                    }
+                    self.expression(value);
+                }
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => (),
+        }
+    }
+
+    // Parameters and the body share one scope, like the environment of a call.
+    fn function(&mut self, declaration: &mut FunctionDeclaration, kind: FunctionKind) {
+        let enclosing = std::mem::replace(&mut self.function, kind);
+        self.begin_scope();
+
+        for param in &declaration.params {
+            self.declare(param);
+            self.define(param);
+            // Callbacks often ignore some of their arguments, that's fine.
+            self.mark_used(param);
+        }
+        self.statements(&mut declaration.body);
+
+        self.end_scope();
+        self.function = enclosing;
+    }
+
+    fn expression(&mut self, expression: &mut Expression) {
+        match expression {
+            Expression::Variable { name, depth, .. } => {
+                if let Some(Local { defined: false, .. }) = self.scopes.last().and_then(|scope| scope.get(&name.lexeme)) {
+                    let error = ResolveError::new(&error_code::READ_IN_OWN_INITIALIZER, name.span, "Resolver.rs", "Can't read local variable in its own initializer.")
+                        .with_help("give the new variable another name");
+                    self.errors.push(error);
+                }
+                *depth = self.resolve_local(name);
+                self.mark_used(name);
+            }
+            // Assigning isn't reading, a variable only ever assigned to is still unused.
+            Expression::Assign { name, value, depth, .. } => {
+                self.expression(value);
+                *depth = self.resolve_local(name);
+            }
+            Expression::Binary { left, right, .. } | Expression::Comma { left, right, .. } | Expression::Logical { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Call { callee, arguments, .. } => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::Conditional { condition, then_branch, else_branch, .. } => {
+                self.expression(condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expression::Get { object, .. } => self.expression(object),
+            Expression::Set { object, value, .. } => {
+                self.expression(value);
+                self.expression(object);
+            }
+            Expression::Super { keyword, depth, .. } => {
+                let message = match self.class {
+                    ClassKind::None => Some("Can't use 'super' outside of a class."),
+                    ClassKind::Class => Some("Can't use 'super' in a class with no superclass."),
+                    ClassKind::Subclass => None,
+                };
+                if let Some(message) = message {
+                    self.errors.push(ResolveError::new(&error_code::SUPER_OUTSIDE_SUBCLASS, keyword.span, "Resolver.rs", message));
+                }
+                *depth = self.resolve_local(keyword);
+            }
+            Expression::This { keyword, depth, .. } => {
+                if self.class == ClassKind::None {
+                    self.errors.push(ResolveError::new(&error_code::THIS_OUTSIDE_CLASS, keyword.span, "Resolver.rs", "Can't use 'this' outside of a class."));
+                }
+                *depth = self.resolve_local(keyword);
+            }
+            Expression::Grouping { expression, .. } => self.expression(expression),
+            Expression::Unary { right, .. } => self.expression(right),
+            Expression::Literal { .. } => (),
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    // Warns about the locals of the scope nobody read, in source order.
+    fn end_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+
+        let mut unused: Vec<(&String, &Local)> = scope.iter().filter(|(name, local)| !local.used && !name.starts_with('_')).collect();
+        unused.sort_by_key(|(_, local)| local.span.start);
+
+        for (name, local) in unused {
+            let warning = Diagnostic::warning(&format!("Unused local variable '{}'.", name))
+                .with_code(error_code::UNUSED_VARIABLE.code)
+                .with_label(local.span, "")
+                .with_help(&format!("if this is intentional, prefix it with an underscore: `_{}`", name));
+            self.warnings.push(warning);
+        }
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+
+        if let Some(previous) = scope.get(&name.lexeme) {
+            let error = ResolveError::new(&error_code::DUPLICATE_DECLARATION, name.span, "Resolver.rs", &format!("Already a variable named '{}' in this scope.", name.lexeme))
+                .with_secondary(previous.span, "first declared here");
+            self.errors.push(error);
+            return;
+        }
+
+        scope.insert(name.lexeme.clone(), Local { span: name.span, defined: false, used: false });
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(local) = self.scopes.last_mut().and_then(|scope| scope.get_mut(&name.lexeme)) {
+            local.defined = true;
+        }
+    }
+
+    // Names the interpreter defines by itself, like `this`, never unused.
+    fn define_implicit(&mut self, name: &str, span: Span) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), Local { span, defined: true, used: true });
+        }
+    }
+
+    fn mark_used(&mut self, name: &Token) {
+        if let Some(local) = self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(&name.lexeme)) {
+            local.used = true;
+        }
+    }
+
+    // None when no scope has the name, it must be a global.
+    fn resolve_local(&self, name: &Token) -> Option<usize> {
+        self.scopes.iter().rev().position(|scope| scope.contains_key(&name.lexeme))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{diagnostic::Severity, exception::BaseException, parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    fn resolve(source: &str) -> (Vec<Stmt>, Vec<ResolveError>, Vec<Diagnostic>) {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let (mut statements, errors) = Parser::new(tokens).parse();
+        assert!(errors.is_empty());
+
+        let (errors, warnings) = Resolver::new().resolve(&mut statements);
+        (statements, errors, warnings)
+    }
+
+    fn depth(statement: &Stmt) -> Option<usize> {
+        match statement {
+            Stmt::Print { expression: Expression::Variable { depth, .. }, .. } => *depth,
+            Stmt::Expression { expression: Expression::Assign { depth, .. }, .. } => *depth,
+            other => panic!("not a variable: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn depths() {
+        let (statements, errors, _) = resolve("var a = 1; { var b = 2; { print b; print a; b = 3; } }");
+
+        assert!(errors.is_empty());
+        let Stmt::Block { statements, .. } = &statements[1] else { panic!() };
+        let Stmt::Block { statements, .. } = &statements[1] else { panic!() };
+        assert_eq!(depth(&statements[0]), Some(1));
+        assert_eq!(depth(&statements[1]), None);
+        assert_eq!(depth(&statements[2]), Some(1));
+    }
+
+    #[test]
+    fn own_initializer() {
+        let (_, errors, _) = resolve("var a = 1; { var a = a + 1; print a; }");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code(), &error_code::READ_IN_OWN_INITIALIZER);
+        assert_eq!(errors[0].span(), Span::new(21, 22, 1, 22));
+    }
+
+    #[test]
+    fn globals_can_use_themselves() {
+        let (_, errors, _) = resolve("var a = 1; var a = a + 1;");
+
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn duplicate_declarations() {
+        let (_, errors, _) = resolve("fun f(a, a) { var b; var b; print a + b; }\n{ var c; { var c; print c; } print c; }");
+
+        let messages: Vec<&str> = errors.iter().map(|error| error.message()).collect();
+        assert_eq!(messages, vec!["Already a variable named 'a' in this scope.", "Already a variable named 'b' in this scope."]);
+        assert_eq!(errors[0].diagnostic().labels[1].span, Span::new(6, 7, 1, 7));
+    }
+
+    #[test]
+    fn top_level_return() {
+        let (_, errors, _) = resolve("return 1;\nfun f() { return 2; }");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code(), &error_code::RETURN_OUTSIDE_FUNCTION);
+        assert_eq!(errors[0].span(), Span::new(0, 6, 1, 1));
+    }
+
+    #[test]
+    fn this_and_initializers() {
+        let (_, errors, _) = resolve("print this;\nfun f() { return this; }\nclass A { init() { return 1; } g() { return this; } h() { fun inner() { return this; } return inner; } }\nclass B { init() { return; } }");
+
+        let codes: Vec<&str> = errors.iter().map(|error| error.code().code).collect();
+        assert_eq!(codes, vec!["T0110", "T0110", "T0109"]);
+    }
+
+    #[test]
+    fn inheritance() {
+        let (_, errors, _) = resolve("class A < A {}\nsuper.f();\nclass B { f() { super.f(); } }\nclass C < B { f() { super.f(); } }");
+
+        let messages: Vec<&str> = errors.iter().map(|error| error.message()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "A class can't inherit from itself.",
+                "Can't use 'super' outside of a class.",
+                "Can't use 'super' in a class with no superclass.",
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_locals() {
+        let source = "var global;\nfun f(unused) { var a; var b = 1; var _c; fun g() {} a = 2; return b; }";
+        let (_, errors, warnings) = resolve(source);
+
+        assert!(errors.is_empty());
+        let messages: Vec<&str> = warnings.iter().map(|warning| warning.message.as_str()).collect();
+        assert_eq!(messages, vec!["Unused local variable 'a'.", "Unused local variable 'g'."]);
+        assert!(matches!(warnings[0].severity, Severity::Warning));
+        assert_eq!(warnings[0].code, Some(error_code::UNUSED_VARIABLE.code));
+    }
+
+    #[test]
+    fn shared_declarations() {
+        let tokens = Scanner::new("class A { m() { var a = 1; print a; } }
+fun f() { var b = 2; print b; }").scan_tokens().unwrap();
+        let (mut statements, _) = Parser::new(tokens).parse();
+        let kept = statements.clone();
+
+        let (errors, _) = Resolver::new().resolve(&mut statements);
+        assert!(errors.is_empty());
+
+        let (Stmt::Class { methods, .. }, Stmt::Function { declaration }) = (&statements[0], &statements[1]) else { panic!() };
+        assert_eq!(depth(&methods[0].body[1]), Some(0));
+        assert_eq!(depth(&declaration.body[1]), Some(0));
+
+        let Stmt::Function { declaration } = &kept[1] else { panic!() };
+        assert_eq!(depth(&declaration.body[1]), None);
+    }
+}
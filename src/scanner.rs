@@ -1,7 +1,11 @@
 use std::str::FromStr;
 
+use num_bigint::BigInt;
+
 use crate::{
-    exception::{self},
+    error_code::{self, ErrorCode},
+    exception::LexError,
+    span::Span,
     token::{Literal, Token, TokenEnum},
 };
 
@@ -11,33 +15,53 @@ pub struct Scanner<'a> {
     tokens: Vec<Token>,
     // The start field points to the first character in the lexeme being scanned,
     // and current points at the character currently being considered.
+    // Both are byte offsets into the source.
     start: usize,
     current: usize,
     line: u32,
+    // Byte offset where the current line begins, used to work out columns.
+    line_start: usize,
+    // Where the lexeme being scanned begins, errors are reported there.
+    start_line: u32,
+    start_column: u32,
+    errors: Vec<LexError>,
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str) -> Self {
         Self {
-            source: source,
+            source,
             tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
+            errors: Vec::new(),
         }
     }
 
-    pub fn scan_tokens(&mut self) -> exception::Result<Vec<Token>> {
+    // Scans the whole source. A bad character doesn't stop the scanner, it keeps going
+    // so every lexical error comes back at once, in the order they appear.
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<LexError>> {
         while !self.is_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column(self.start);
             self.scan_token();
         }
 
+        if !self.errors.is_empty() {
+            return Err(self.errors.clone());
+        }
+
+        let end = Span::new(self.current, self.current, self.line, self.column(self.current));
         self.tokens.push(Token::new(
             TokenEnum::EOF,
             "",
             Default::default(),
-            self.line,
+            end,
         ));
 
         Ok(self.tokens.clone())
@@ -57,10 +81,13 @@ impl<'a> Scanner<'a> {
             ')' => self.add_token(TokenEnum::RightParen),
             '{' => self.add_token(TokenEnum::LeftBrace),
             '}' => self.add_token(TokenEnum::RightBrace),
+            ':' => self.add_token(TokenEnum::Colon),
             ',' => self.add_token(TokenEnum::Comma),
             '.' => self.add_token(TokenEnum::Dot),
             '-' => self.add_token(TokenEnum::Minus),
+            '%' => self.add_token(TokenEnum::Percent),
             '+' => self.add_token(TokenEnum::Plus),
+            '?' => self.add_token(TokenEnum::Question),
             ';' => self.add_token(TokenEnum::SemiColon),
             '*' => self.add_token(TokenEnum::Star),
 
@@ -98,13 +125,13 @@ impl<'a> Scanner<'a> {
             }
             '/' => self.comments(),
 
-            '\n' => self.line += 1,
+            '\n' => self.new_line(),
 
             ' ' | '\r' | '\t' => (),
 
             '"' => self.string(),
 
-            __ => {
+            _ => {
                 // let char = self.peek().unwrap();
 
                 if self.is_digit(c) {
@@ -112,15 +139,36 @@ impl<'a> Scanner<'a> {
                 } else if self.is_alpha(c) {
                     self.identifier();
                 } else {
-                    exception::Exception::new(self.line, "", "Unexpected character.");
+                    self.error(&error_code::UNEXPECTED_CHARACTER, &format!("Unexpected character '{}'.", c));
                 }
             }
         }
     }
 
     fn advance(&mut self) -> Option<char> {
-        self.current += 1;
-        self.get_char(self.current - 1)
+        let c = self.get_char(self.current)?;
+        self.current += c.len_utf8();
+        Some(c)
+    }
+
+    // Must be called right after consuming a '\n'.
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
+    // Columns count characters, not bytes, starting at 1.
+    fn column(&self, offset: usize) -> u32 {
+        self.source[self.line_start..offset].chars().count() as u32 + 1
+    }
+
+    // The span of the lexeme scanned so far.
+    fn span(&self) -> Span {
+        Span::new(self.start, self.current, self.start_line, self.start_column)
+    }
+
+    fn error(&mut self, code: &'static ErrorCode, message: &str) {
+        self.errors.push(LexError::new(code, self.span(), "", message));
     }
 
     fn add_token(&mut self, token_type: TokenEnum) {
@@ -131,7 +179,7 @@ impl<'a> Scanner<'a> {
         let text = &self.source[self.start..self.current];
 
         self.tokens
-            .push(Token::new(token_type, text, literal, self.line))
+            .push(Token::new(token_type, text, literal, self.span()))
     }
 
     fn match_char(&mut self, expected: char) -> bool {
@@ -139,11 +187,11 @@ impl<'a> Scanner<'a> {
             return false;
         }
 
-        if self.get_char(self.current).unwrap() != expected {
+        if self.get_char(self.current) != Some(expected) {
             return false;
         }
 
-        self.current += 1;
+        self.current += expected.len_utf8();
 
         true
     }
@@ -155,20 +203,27 @@ impl<'a> Scanner<'a> {
                 self.advance();
             }
         } else if self.match_char('*') {
-            while self.peek().unwrap() != '*' && self.peek_next().unwrap() != '/' && !self.is_end()
-            {
-                if self.peek().unwrap() == '\n' {
-                    self.line += 1;
-                };
-                self.advance();
+            while !(self.peek().unwrap() == '*' && self.peek_next().unwrap() == '/') {
+                if self.is_end() {
+                    self.error(&error_code::UNTERMINATED_COMMENT, "Unterminated block comment.");
+                    return;
+                }
+
+                if self.advance() == Some('\n') {
+                    self.new_line();
+                }
             }
+
+            // The closing */
+            self.advance();
+            self.advance();
         } else {
             self.add_token(TokenEnum::Slash);
         }
     }
 
     fn get_char(&self, index: usize) -> Option<char> {
-        self.source.chars().nth(index)
+        self.source.get(index..)?.chars().next()
     }
 
     fn peek(&self) -> Option<char> {
@@ -181,14 +236,13 @@ impl<'a> Scanner<'a> {
 
     fn string(&mut self) {
         while self.peek().unwrap() != '"' && !self.is_end() {
-            if self.peek().unwrap() == '\n' {
-                self.line += 1;
+            if self.advance() == Some('\n') {
+                self.new_line();
             }
-            self.advance();
         }
 
         if self.is_end() {
-            exception::Exception::new(self.line, "", "Unterminated string.");
+            self.error(&error_code::UNTERMINATED_STRING, "Unterminated string.");
             return;
         }
 
@@ -199,28 +253,116 @@ impl<'a> Scanner<'a> {
     }
 
     fn number(&mut self) {
-        while self.is_digit(self.peek().unwrap()) {
-            self.advance();
+        if self.source[self.start..].starts_with('0') {
+            let radix = match self.peek().unwrap() {
+                'x' => Some((16, "hexadecimal")),
+                'o' => Some((8, "octal")),
+                'b' => Some((2, "binary")),
+                _ => None,
+            };
+
+            if let Some((radix, name)) = radix {
+                self.advance();
+                return self.radix_number(radix, name);
+            }
         }
 
+        self.digits();
+
+        let mut float = false;
+
         // Fractional numbers
         if self.peek().unwrap() == '.' && self.is_digit(self.peek_next().unwrap()) {
+            float = true;
             self.advance();
+            self.digits();
+        }
 
-            while self.is_digit(self.peek().unwrap()) {
+        // Exponents, `1e3` or `2.5E-3`. An `e` without digits after it isn't part of the number.
+        if matches!(self.peek().unwrap(), 'e' | 'E') {
+            let rest = &self.source[self.current + 1..];
+            let digits = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+            if digits.starts_with(|c: char| c.is_ascii_digit()) {
+                float = true;
                 self.advance();
+                if matches!(self.peek().unwrap(), '+' | '-') {
+                    self.advance();
+                }
+
+                self.digits();
+            }
+        }
+
+        let text = &self.source[self.start..self.current];
+        if !separated(text, |c| c.is_ascii_digit()) {
+            return self.misplaced_separator();
+        }
+
+        let text = text.replace('_', "");
+        if float {
+            match text.parse::<f64>() {
+                Ok(value) if value.is_finite() => self.add_token_base(TokenEnum::Number, Literal::Float(value)),
+                _ => self.error(&error_code::INVALID_NUMBER, &format!("Number literal '{}' is too large.", text)),
             }
+            return;
         }
 
-        let value: i64 = self.source[self.start..self.current].parse().unwrap();
-        self.add_token_base(TokenEnum::Number, Literal::Number(value))
+        // Any number of digits works, the ones too long for an i64 become bignums.
+        let literal = match text.parse::<i64>() {
+            Ok(value) => Literal::Number(value),
+            Err(_) => Literal::Big(text.parse::<BigInt>().expect("only digits were scanned")),
+        };
+        self.add_token_base(TokenEnum::Number, literal);
     }
 
-    fn peek_next(&self) -> Option<char> {
-        if self.current + 1 >= self.source.len() {
-            return Some('\0');
+    // Decimal digits, `_` can separate them.
+    fn digits(&mut self) {
+        while self.is_digit(self.peek().unwrap()) || self.peek().unwrap() == '_' {
+            self.advance();
         }
-        self.get_char(self.current + 1)
+    }
+
+    // `0x`, `0o` and `0b`, the prefix is already consumed. Letters and digits right after
+    // it all belong to the literal, so `0b102` says which digit is wrong instead of
+    // becoming `0b10` followed by `2`.
+    fn radix_number(&mut self, radix: u32, name: &str) {
+        while self.is_alpha_numeric(self.peek().unwrap()) {
+            self.advance();
+        }
+
+        let text = &self.source[self.start..self.current];
+        let digits = &text[2..];
+        if !digits.chars().any(|c| c != '_') {
+            return self.error(&error_code::INVALID_NUMBER, &format!("Number literal '{}' has no digits.", text));
+        }
+
+        if !separated(digits, |c| c.is_ascii_alphanumeric()) {
+            return self.misplaced_separator();
+        }
+
+        if let Some(digit) = digits.chars().find(|c| *c != '_' && !c.is_digit(radix)) {
+            let message = format!("Digit '{}' is out of range for the {} literal '{}'.", digit, name, text);
+            return self.error(&error_code::INVALID_NUMBER, &message);
+        }
+
+        let digits = digits.replace('_', "");
+        let literal = match i64::from_str_radix(&digits, radix) {
+            Ok(value) => Literal::Number(value),
+            Err(_) => Literal::Big(BigInt::parse_bytes(digits.as_bytes(), radix).expect("digits were checked")),
+        };
+        self.add_token_base(TokenEnum::Number, literal);
+    }
+
+    fn misplaced_separator(&mut self) {
+        let text = &self.source[self.start..self.current];
+        let message = format!("The '_' in number literal '{}' must be between two digits.", text);
+        self.error(&error_code::INVALID_NUMBER, &message);
+    }
+
+    fn peek_next(&self) -> Option<char> {
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        Some(chars.next().unwrap_or('\0'))
     }
 
     fn identifier(&mut self) {
@@ -230,7 +372,7 @@ impl<'a> Scanner<'a> {
 
         let text = &self.source[self.start..self.current];
 
-        let token_type = match TokenEnum::from_str(&text) {
+        let token_type = match TokenEnum::from_str(text) {
             Ok(token_type) => token_type,
             Err(()) => TokenEnum::Identifier,
         };
@@ -239,7 +381,7 @@ impl<'a> Scanner<'a> {
     }
 
     fn is_alpha(&self, c: char) -> bool {
-        (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+        c.is_ascii_alphabetic() || c == '_'
     }
 
     fn is_alpha_numeric(&self, c: char) -> bool {
@@ -247,28 +389,12 @@ impl<'a> Scanner<'a> {
     }
 
     fn is_digit(&self, c: char) -> bool {
-        c >= '0' && c <= '9'
+        c.is_ascii_digit()
     }
 
     // Pretty much the same as I was doing before, happy
     fn is_end(&self) -> bool {
-        &self.current >= &self.source.len()
-    }
-
-    fn is_char(&self, c: char) -> bool {
-        (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z')
-    }
-
-    fn is_operator(&self, c: char) -> bool {
-        c == '>' || c == '<' || c == '=' || c == '!'
-    }
-
-    fn is_white_space(&self, c: char) -> bool {
-        c == ' ' || c == '\t' || c == '\r'
-    }
-
-    fn is_char_end(&self, c: char) -> bool {
-        c == '\0'
+        self.current >= self.source.len()
     }
 
     // pub fn back(&mut self) {
@@ -277,8 +403,16 @@ impl<'a> Scanner<'a> {
     // }
 }
 
+// Every `_` has a digit on both sides.
+fn separated(text: &str, is_digit: fn(char) -> bool) -> bool {
+    let chars: Vec<char> = text.chars().collect();
+    chars.iter().enumerate().all(|(i, c)| *c != '_' || (i > 0 && i + 1 < chars.len() && is_digit(chars[i - 1]) && is_digit(chars[i + 1])))
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::exception::BaseException;
+
     use super::*;
 
     #[test]
@@ -297,6 +431,187 @@ mod tests {
         assert!(scanner.is_err());
     }
 
+    #[test]
+    fn collects_every_error() {
+        let mut binding = Scanner::new("var a = 1 # 2;\nvar b = @;\n  \"open");
+        let errors = binding.scan_tokens().unwrap_err();
+
+        let found: Vec<(u32, u32, String)> = errors
+            .iter()
+            .map(|error| (error.line(), error.column(), error.message().to_string()))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (1, 11, "Unexpected character '#'.".to_string()),
+                (2, 9, "Unexpected character '@'.".to_string()),
+                (3, 3, "Unterminated string.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_scanning_after_an_error() {
+        let mut binding = Scanner::new("1 ~ 2");
+        binding.scan_tokens().unwrap_err();
+
+        let types: Vec<TokenEnum> = binding.tokens.iter().map(|token| token.token_type).collect();
+        assert_eq!(types, vec![TokenEnum::Number, TokenEnum::Number]);
+    }
+
+    #[test]
+    fn token_spans() {
+        let mut binding = Scanner::new("var result = 10;\n  \"ab\"");
+        let tokens = binding.scan_tokens().unwrap();
+
+        let spans: Vec<Span> = tokens.iter().map(|token| token.span).collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(0, 3, 1, 1),
+                Span::new(4, 10, 1, 5),
+                Span::new(11, 12, 1, 12),
+                Span::new(13, 15, 1, 14),
+                Span::new(15, 16, 1, 16),
+                Span::new(19, 23, 2, 3),
+                Span::new(23, 23, 2, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn unicode_columns() {
+        let mut binding = Scanner::new("\"ção\" é");
+        let errors = binding.scan_tokens().unwrap_err();
+
+        assert_eq!(errors[0].column(), 7);
+        assert_eq!(errors[0].span(), Span::new(8, 10, 1, 7));
+        assert_eq!(binding.tokens[0].literal, Literal::String("ção".to_string()));
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        let mut binding = Scanner::new("1 /* never\n closed");
+        let errors = binding.scan_tokens().unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "Unterminated block comment.");
+        assert_eq!(errors[0].code(), &error_code::UNTERMINATED_COMMENT);
+    }
+
+    #[test]
+    fn loop_keywords() {
+        let mut binding = Scanner::new("outer: while (true) { break outer; continue; }");
+        let types: Vec<TokenEnum> = binding.scan_tokens().unwrap().iter().map(|token| token.token_type).collect();
+
+        assert_eq!(&types[..2], [TokenEnum::Identifier, TokenEnum::Colon]);
+        assert!(types.contains(&TokenEnum::Break));
+        assert!(types.contains(&TokenEnum::Continue));
+    }
+
+    #[test]
+    fn numbers() {
+        let mut binding = Scanner::new("10 2.75 1e-3 2.5E+2 7e 1.x");
+        let literals: Vec<Literal> = binding.scan_tokens().unwrap().into_iter().map(|token| token.literal).collect();
+
+        assert_eq!(
+            literals,
+            vec![
+                Literal::Number(10),
+                Literal::Float(2.75),
+                Literal::Float(0.001),
+                Literal::Float(250.0),
+                Literal::Number(7),
+                Literal::None,
+                Literal::Number(1),
+                Literal::None,
+                Literal::None,
+                Literal::None,
+            ]
+        );
+    }
+
+    #[test]
+    fn long_integers() {
+        let digits = "1".repeat(60);
+        let mut binding = Scanner::new(&digits);
+        let tokens = binding.scan_tokens().unwrap();
+
+        assert_eq!(tokens[0].literal, Literal::Big(digits.parse().unwrap()));
+        assert_eq!(tokens[0].lexeme, digits);
+    }
+
+    #[test]
+    fn radix_numbers() {
+        let mut binding = Scanner::new("0xff 0o17 0b1010 0b1111_0000 0xDEAD_beef 0x1_0000_0000_0000_0000");
+        let literals: Vec<Literal> = binding.scan_tokens().unwrap().into_iter().map(|token| token.literal).collect();
+
+        assert_eq!(
+            literals,
+            vec![
+                Literal::Number(255),
+                Literal::Number(15),
+                Literal::Number(10),
+                Literal::Number(240),
+                Literal::Number(0xDEAD_BEEF),
+                Literal::Big("18446744073709551616".parse().unwrap()),
+                Literal::None,
+            ]
+        );
+    }
+
+    #[test]
+    fn separators() {
+        let mut binding = Scanner::new("1_000_000 2_5.0_5 1_0e1_0");
+        let literals: Vec<Literal> = binding.scan_tokens().unwrap().into_iter().map(|token| token.literal).collect();
+
+        assert_eq!(literals, vec![Literal::Number(1_000_000), Literal::Float(25.05), Literal::Float(1e11), Literal::None]);
+    }
+
+    #[test]
+    fn malformed_numbers() {
+        let cases = [
+            ("0x", "Number literal '0x' has no digits."),
+            ("0o", "Number literal '0o' has no digits."),
+            ("0b;", "Number literal '0b' has no digits."),
+            ("0x_", "Number literal '0x_' has no digits."),
+            ("0xfg", "Digit 'g' is out of range for the hexadecimal literal '0xfg'."),
+            ("0o78", "Digit '8' is out of range for the octal literal '0o78'."),
+            ("0b102", "Digit '2' is out of range for the binary literal '0b102'."),
+            ("1__000", "The '_' in number literal '1__000' must be between two digits."),
+            ("1_", "The '_' in number literal '1_' must be between two digits."),
+            ("1_.5", "The '_' in number literal '1_.5' must be between two digits."),
+            ("0x_ff", "The '_' in number literal '0x_ff' must be between two digits."),
+        ];
+
+        for (source, message) in cases {
+            let mut binding = Scanner::new(source);
+            let errors = binding.scan_tokens().unwrap_err();
+
+            assert_eq!(errors[0].code(), &error_code::INVALID_NUMBER, "{}", source);
+            assert_eq!(errors[0].message(), message);
+            assert_eq!(errors[0].span().start, 0);
+        }
+    }
+
+    #[test]
+    fn float_too_large() {
+        let mut binding = Scanner::new("1e400");
+        let errors = binding.scan_tokens().unwrap_err();
+
+        assert_eq!(errors[0].code(), &error_code::INVALID_NUMBER);
+        assert_eq!(errors[0].message(), "Number literal '1e400' is too large.");
+    }
+
+    #[test]
+    fn conditional() {
+        let mut binding = Scanner::new("a ? b : c");
+        let types: Vec<TokenEnum> = binding.scan_tokens().unwrap().iter().map(|token| token.token_type).collect();
+
+        assert_eq!(types, [TokenEnum::Identifier, TokenEnum::Question, TokenEnum::Identifier, TokenEnum::Colon, TokenEnum::Identifier, TokenEnum::EOF]);
+    }
+
     #[test]
     fn comments() {
         let mut binding = Scanner::new(
@@ -306,6 +621,9 @@ mod tests {
         let scanner = binding.scan_tokens();
 
         assert!(scanner.is_ok());
+
+        let types: Vec<TokenEnum> = scanner.unwrap().iter().map(|token| token.token_type).collect();
+        assert_eq!(types, vec![TokenEnum::Var, TokenEnum::Equal, TokenEnum::String, TokenEnum::EOF]);
     }
 }
 
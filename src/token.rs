@@ -1,5 +1,10 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
+use num_bigint::BigInt;
+
+use crate::span::Span;
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum TokenEnum {
     // Single-character tokens.
@@ -7,10 +12,13 @@ pub enum TokenEnum {
     RightParen,
     LeftBrace,
     RightBrace,
+    Colon,
     Comma,
     Dot,
     Minus,
+    Percent,
     Plus,
+    Question,
     SemiColon,
     Slash,
     Star,
@@ -32,7 +40,9 @@ pub enum TokenEnum {
 
     // Keywords
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
@@ -55,7 +65,7 @@ pub struct Token {
     pub token_type: TokenEnum,
     pub lexeme: String,
     pub literal: Literal,
-    pub line: u32
+    pub span: Span,
 }
 
 impl FromStr for TokenEnum {
@@ -65,7 +75,9 @@ impl FromStr for TokenEnum {
         match s {
             "String" | "string" => Ok(TokenEnum::String),
             "And"    | "and"    => Ok(TokenEnum::And),
+            "Break"  | "break"  => Ok(TokenEnum::Break),
             "Class"  | "class"  => Ok(TokenEnum::Class),
+            "Continue" | "continue" => Ok(TokenEnum::Continue),
             "Else"   | "else"   => Ok(TokenEnum::Else),
             "False"  | "false"  => Ok(TokenEnum::False),
             "Fun"    | "fun"    => Ok(TokenEnum::Fun),
@@ -92,44 +104,45 @@ impl FromStr for TokenEnum {
 //     }
 // }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum Literal {
     String(String),
     Number(i64),
+    // Integer literals too long for an i64.
+    Big(BigInt),
+    Float(f64),
     Bool(bool),
+    #[default]
     None
 }
 
-impl Default for Literal {
-    fn default() -> Self {
-        Literal::None
-    }
-}
-
-impl ToString for Literal {
-
-    fn to_string(&self) -> String {
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Literal::None => String::new(),
-            Literal::Bool(value) => String::from(value.to_string()),
-            Literal::Number(value) => String::from(value.to_string()),
-            Literal::String(value) => String::from(value),
+            Literal::None => write!(f, "null"),
+            Literal::Bool(value) => write!(f, "{}", value),
+            Literal::Number(value) => write!(f, "{}", value),
+            Literal::Big(value) => write!(f, "{}", value),
+            Literal::Float(value) => write!(f, "{:?}", value),
+            Literal::String(value) => write!(f, "{}", value),
         }
     }
 }
 
 impl Token {
-    pub fn new(token_type: TokenEnum, lexeme: &str, literal: Literal, line: u32) -> Self {
+    pub fn new(token_type: TokenEnum, lexeme: &str, literal: Literal, span: Span) -> Self {
         let lexeme = lexeme.to_string();
         Self {
             token_type,
             lexeme,
             literal,
-            line,
+            span,
         }
     }
+}
 
-    pub fn to_string(&self) -> String {
-        format!("{:?} - {} - {:?}", self.token_type, self.lexeme, self.literal)
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} - {} - {:?} - {}", self.token_type, self.lexeme, self.literal, self.span)
     }
 }
@@ -0,0 +1,208 @@
+use std::{cell::RefCell, cmp::Ordering, collections::HashMap, fmt, rc::Rc};
+
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::{ast::FunctionDeclaration, environment::Environment, token::Literal};
+
+// What expressions evaluate to. Literals from the source turn into one of the first
+// four, the rest only exist while the program runs.
+#[derive(Debug, Clone)]
+pub enum Value {
+    String(String),
+    // An integer.
+    Number(i64),
+    // An integer that doesn't fit in an i64, never one that does. Build it with
+    // `Value::from(BigInt)`, which gives a Number when it can.
+    Big(BigInt),
+    Float(f64),
+    Bool(bool),
+    None,
+    Function(Rc<Function>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<Class>),
+    // Shared and mutable, every copy of the value sees the same fields.
+    Instance(Rc<RefCell<Instance>>),
+}
+
+// A function declared in the script, with the scope it was declared in. Calls run
+// inside that scope, so the function keeps seeing (and changing) its variables after
+// the block that declared them is done.
+pub struct Function {
+    pub declaration: Rc<FunctionDeclaration>,
+    pub closure: Rc<RefCell<Environment>>,
+    // `init` methods give back the instance whatever they return.
+    pub is_initializer: bool,
+}
+
+// Calling a class makes an instance of it.
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>,
+    pub methods: HashMap<String, Rc<Function>>,
+}
+
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: HashMap<String, Value>,
+}
+
+// A function written in Rust and given to every script, like `clock`.
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&[Value]) -> Value,
+}
+
+impl Value {
+    // How many arguments the value takes when called, None when it can't be called.
+    pub fn arity(&self) -> Option<usize> {
+        match self {
+            Value::Function(function) => Some(function.declaration.params.len()),
+            Value::Native(native) => Some(native.arity),
+            Value::Class(class) => Some(class.find_method("init").map_or(0, |init| init.declaration.params.len())),
+            _ => None,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Number(_) | Value::Big(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::None => "null",
+            Value::Function(_) | Value::Native(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+        }
+    }
+}
+
+impl Function {
+    // A copy of the method whose `this` is `instance`, what `instance.method` gives.
+    pub fn bind(&self, instance: Value) -> Function {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        environment.define("this", instance);
+
+        Function {
+            declaration: self.declaration.clone(),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+impl Class {
+    // Looks in the class first, then up the superclasses.
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+        match (self.methods.get(name), &self.superclass) {
+            (Some(method), _) => Some(method.clone()),
+            (None, Some(superclass)) => superclass.find_method(name),
+            (None, None) => None,
+        }
+    }
+}
+
+impl From<BigInt> for Value {
+    fn from(value: BigInt) -> Self {
+        match value.to_i64() {
+            Some(value) => Value::Number(value),
+            None => Value::Big(value),
+        }
+    }
+}
+
+impl From<Literal> for Value {
+    fn from(literal: Literal) -> Self {
+        match literal {
+            Literal::String(value) => Value::String(value),
+            Literal::Number(value) => Value::Number(value),
+            Literal::Big(value) => Value::from(value),
+            Literal::Float(value) => Value::Float(value),
+            Literal::Bool(value) => Value::Bool(value),
+            Literal::None => Value::None,
+        }
+    }
+}
+
+// Functions are only equal to themselves. Integers and floats compare by value, `1 == 1.0`.
+// Orders an integer and a float exactly, converting the integer to a float could round
+// it onto the float. None when the float is NaN.
+pub fn compare_integer_float(integer: &BigInt, float: f64) -> Option<Ordering> {
+    if float.is_infinite() {
+        return Some(if float > 0.0 { Ordering::Less } else { Ordering::Greater });
+    }
+
+    // Without its fraction a finite float is an integer, and a BigInt holds it exactly.
+    let floor = BigInt::from_f64(float.floor())?;
+    match integer.cmp(&floor) {
+        Ordering::Equal if float.fract() != 0.0 => Some(Ordering::Less),
+        ordering => Some(ordering),
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::Float(left), Value::Float(right)) => left == right,
+            (Value::Number(left), Value::Float(right)) | (Value::Float(right), Value::Number(left)) => compare_integer_float(&BigInt::from(*left), *right) == Some(Ordering::Equal),
+            // A Big is never small enough to equal a Number.
+            (Value::Big(left), Value::Big(right)) => left == right,
+            (Value::Big(left), Value::Float(right)) | (Value::Float(right), Value::Big(left)) => compare_integer_float(left, *right) == Some(Ordering::Equal),
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::None, Value::None) => true,
+            (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+            (Value::Native(left), Value::Native(right)) => Rc::ptr_eq(left, right),
+            (Value::Class(left), Value::Class(right)) => Rc::ptr_eq(left, right),
+            (Value::Instance(left), Value::Instance(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::None => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::Big(value) => write!(f, "{}", value),
+            // Debug always shows floats as floats, `3.0` instead of `3`, and switches to
+            // exponents for very big or small ones.
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Function(function) => write!(f, "{:?}", function),
+            Value::Native(native) => write!(f, "{:?}", native),
+            Value::Class(class) => write!(f, "{:?}", class),
+            Value::Instance(instance) => write!(f, "{:?}", instance.borrow()),
+        }
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
+
+// Not derived, an instance can have itself in a field.
+impl fmt::Debug for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}